@@ -0,0 +1,54 @@
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::fmt::Write;
+use std::hint::black_box;
+
+/// Builds a document with `n` blocks that use every kind of value.
+fn document(n: usize) -> String {
+    let mut input = String::new();
+
+    for i in 0..n {
+        writeln!(
+            input,
+            r#"# package {i}
+Package {{
+    name: text("package-{i}")
+    description: text(r"a package with \no escapes")
+    version: int({i})
+    weight: float(-{i}.25e2)
+    stable: bool(yes)
+    license: none
+    authors: list(text("first \"author\""), text("second\tauthor"))
+    deps: map("serde": text("1.0"), "indexmap": text("2.13"))
+    meta: Meta {{ checked: bool(false) score: float(inf) }}
+}}"#
+        )
+        .unwrap();
+    }
+
+    input
+}
+
+fn parse(c: &mut Criterion) {
+    let input = document(1_000);
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+
+    group.bench_function("parser", |b| {
+        b.iter(|| libsirt::parse_input(black_box(&input)).unwrap())
+    });
+    group.bench_function("pest", |b| {
+        b.iter(|| libsirt::grammar::parse_input(black_box(&input)).unwrap())
+    });
+    group.bench_function("parser spanned", |b| {
+        b.iter(|| libsirt::parse_input_spanned(black_box(&input)).unwrap())
+    });
+    group.bench_function("pest spanned", |b| {
+        b.iter(|| libsirt::grammar::parse_input_spanned(black_box(&input)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
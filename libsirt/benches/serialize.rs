@@ -0,0 +1,150 @@
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counts every allocation made by the benchmarks.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Measures the number of allocations instead of the time taken.
+struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> u64 {
+        ALLOCATIONS.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: u64) -> u64 {
+        ALLOCATIONS.load(Ordering::Relaxed) - start
+    }
+
+    fn add(&self, v1: &u64, v2: &u64) -> u64 {
+        v1 + v2
+    }
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn to_f64(&self, value: &u64) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationsFormatter
+    }
+}
+
+struct AllocationsFormatter;
+
+impl ValueFormatter for AllocationsFormatter {
+    fn scale_values(&self, _typical: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let n = match throughput {
+            Throughput::Bytes(n) | Throughput::BytesDecimal(n) | Throughput::Elements(n) => *n,
+        };
+        for value in values {
+            *value /= n as f64;
+        }
+        "allocs/elem"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+#[derive(Serialize)]
+struct Package {
+    name: String,
+    version: i64,
+    weight: f64,
+    stable: bool,
+    authors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Registry {
+    packages: Vec<Package>,
+}
+
+/// Builds a block with a list of `n` packages.
+fn registry(n: usize) -> Registry {
+    let packages = (0..n)
+        .map(|i| Package {
+            name: format!("package-{i}"),
+            version: i as i64,
+            weight: i as f64 * -0.25,
+            stable: i % 2 == 0,
+            authors: vec!["first \"author\"".to_string(), "second".to_string()],
+        })
+        .collect();
+
+    Registry { packages }
+}
+
+fn serialize(c: &mut Criterion<Allocations>) {
+    let registry = registry(10_000);
+
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Elements(registry.packages.len() as u64));
+
+    group.bench_function("to_string", |b| {
+        b.iter(|| libsirt::to_string("Registry", black_box(&registry)).unwrap())
+    });
+    group.bench_function("to_pretty_string", |b| {
+        b.iter(|| libsirt::to_pretty_string("Registry", black_box(&registry)).unwrap())
+    });
+    group.bench_function("to_writer", |b| {
+        b.iter(|| libsirt::to_writer(io::sink(), "Registry", black_box(&registry)).unwrap())
+    });
+    group.bench_function("to_writer_pretty", |b| {
+        b.iter(|| libsirt::to_writer_pretty(io::sink(), "Registry", black_box(&registry)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    // the count is the same for every run, which
+    // the density plots cannot show
+    config = Criterion::default().with_measurement(Allocations).without_plots();
+    targets = serialize
+}
+criterion_main!(benches);
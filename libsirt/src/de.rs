@@ -1,14 +1,64 @@
 use serde::Deserialize;
-use serde::de::{self, Deserializer, Error, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::de::{
+    self, Deserializer, EnumAccess, Error, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
 
-use crate::error::SirtDeserializeError;
-use crate::{Block, Value, parse_input};
+use crate::error::{Error as SirtError, PathSegment, SirtDeserializeError};
+use crate::options::ParseOptions;
+use crate::se::pascal_case;
+use crate::span::{BlockSpans, SourceMap, Span};
+use crate::value::{BLOCK_TOKEN, MAP_TOKEN, VALUE_TOKEN};
+use crate::{Block, BlockReader, Value, parse_input_spanned_with};
 
-struct ListAccess<'a> {
-    iter: std::slice::Iter<'a, Value>,
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::io::BufRead;
+
+fn invalid_type(expected: &str, found: &Value) -> SirtDeserializeError {
+    SirtDeserializeError::custom(format!("expected {expected}, found {}", found.type_name()))
+}
+
+/// Visits text as borrowed from the input when it can be,
+/// so that `&'de str` fields can be deserialized.
+fn visit_text<'de, V>(text: &Cow<'de, str>, visitor: V) -> Result<V::Value, SirtDeserializeError>
+where
+    V: Visitor<'de>,
+{
+    match text {
+        Cow::Borrowed(text) => visitor.visit_borrowed_str(text),
+        Cow::Owned(text) => visitor.visit_str(text),
+    }
+}
+
+/// Deserializes a field name or map key.
+struct KeyDeserializer<'a, 'de> {
+    key: &'a Cow<'de, str>,
+}
+
+impl<'a, 'de> Deserializer<'de> for KeyDeserializer<'a, 'de> {
+    type Error = SirtDeserializeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visit_text(self.key, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct ListAccess<'a, 'de> {
+    iter: std::slice::Iter<'a, Value<'de>>,
+    index: usize,
 }
 
-impl<'a, 'de> SeqAccess<'de> for ListAccess<'a> {
+impl<'a, 'de> SeqAccess<'de> for ListAccess<'a, 'de> {
     type Error = SirtDeserializeError;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
@@ -17,20 +67,28 @@ impl<'a, 'de> SeqAccess<'de> for ListAccess<'a> {
     {
         match self.iter.next() {
             Some(value) => {
+                let index = self.index;
+                self.index += 1;
+
                 let de = ValueDeserializer { value };
-                seed.deserialize(de).map(Some)
+                seed.deserialize(de)
+                    .map(Some)
+                    .map_err(|err| err.prepend(PathSegment::Index(index)))
             }
             None => Ok(None),
         }
     }
 }
 
-struct BlockMapAccess<'a> {
-    iter: std::collections::hash_map::Iter<'a, String, Value>,
-    value: Option<&'a Value>,
+struct BlockMapAccess<'a, 'de> {
+    iter: indexmap::map::Iter<'a, Cow<'de, str>, Value<'de>>,
+    value: Option<(&'a str, &'a Value<'de>)>,
+    /// Whether the entries are the fields of a block,
+    /// rather than the entries of a map.
+    fields: bool,
 }
 
-impl<'de, 'a> MapAccess<'de> for BlockMapAccess<'a> {
+impl<'de, 'a> MapAccess<'de> for BlockMapAccess<'a, 'de> {
     type Error = SirtDeserializeError;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
@@ -38,8 +96,8 @@ impl<'de, 'a> MapAccess<'de> for BlockMapAccess<'a> {
         K: de::DeserializeSeed<'de>,
     {
         if let Some((key, value)) = self.iter.next() {
-            self.value = Some(value);
-            seed.deserialize(key.as_str().into_deserializer()).map(Some)
+            self.value = Some((key, value));
+            seed.deserialize(KeyDeserializer { key }).map(Some)
         } else {
             Ok(None)
         }
@@ -49,34 +107,207 @@ impl<'de, 'a> MapAccess<'de> for BlockMapAccess<'a> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let value = self
+        let (key, value) = self
             .value
             .take()
             .ok_or(SirtDeserializeError::custom("MapAccess error"))?;
 
         seed.deserialize(ValueDeserializer { value })
+            .map_err(|err| {
+                let segment = if self.fields {
+                    PathSegment::Field(key.to_string())
+                } else {
+                    PathSegment::Key(key.to_string())
+                };
+
+                err.prepend(segment)
+            })
     }
 }
 
-pub struct BlockDeserializer<'a> {
-    block: &'a Block,
+/// Access to a block as its name and its fields, for
+/// deserializing a [Block] itself.
+struct BlockEntriesAccess<'a, 'de> {
+    block: &'a Block<'de>,
+    entry: usize,
 }
 
-impl<'de, 'a> Deserializer<'de> for BlockDeserializer<'a> {
+impl<'de, 'a> MapAccess<'de> for BlockEntriesAccess<'a, 'de> {
+    type Error = SirtDeserializeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let key = match self.entry {
+            0 => "name",
+            1 => "fields",
+            _ => return Ok(None),
+        };
+
+        self.entry += 1;
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.entry {
+            1 => seed.deserialize(KeyDeserializer {
+                key: &self.block.name,
+            }),
+            _ => seed.deserialize(BlockDeserializer { block: self.block }),
+        }
+    }
+}
+
+/// Access to a block or a map as a map with a single entry,
+/// keyed by [BLOCK_TOKEN] or [MAP_TOKEN], so that a [Value]
+/// can tell the two apart.
+struct TaggedAccess<D> {
+    token: &'static str,
+    value: Option<D>,
+}
+
+impl<'de, D> MapAccess<'de> for TaggedAccess<D>
+where
+    D: Deserializer<'de, Error = SirtDeserializeError>,
+{
+    type Error = SirtDeserializeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.value {
+            Some(_) => seed.deserialize(self.token.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .ok_or(SirtDeserializeError::custom("MapAccess error"))?;
+        seed.deserialize(value)
+    }
+}
+
+/// Access to an enum variant, which is either stored
+/// as a single entry map, or as a block named after
+/// a struct variant.
+enum EnumVariant<'a, 'de> {
+    Map(&'a str, &'a Value<'de>),
+    Block(&'a Block<'de>),
+}
+
+impl<'de, 'a> EnumAccess<'de> for EnumVariant<'a, 'de> {
+    type Error = SirtDeserializeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let name = match self {
+            EnumVariant::Map(name, _) => name,
+            EnumVariant::Block(block) => block.get_name(),
+        };
+
+        let variant = seed.deserialize(name.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for EnumVariant<'a, 'de> {
+    type Error = SirtDeserializeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self {
+            EnumVariant::Map(_, Value::None) => Ok(()),
+            EnumVariant::Map(name, other) => Err(SirtDeserializeError::custom(format!(
+                "expected unit variant '{name}', found {}",
+                other.type_name()
+            ))),
+            EnumVariant::Block(block) => Err(SirtDeserializeError::custom(format!(
+                "expected unit variant, found block '{}'",
+                block.get_name()
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self {
+            EnumVariant::Map(_, value) => seed.deserialize(ValueDeserializer { value }),
+            EnumVariant::Block(block) => seed.deserialize(BlockDeserializer { block }),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            EnumVariant::Map(_, value) => ValueDeserializer { value }.deserialize_seq(visitor),
+            EnumVariant::Block(block) => Err(SirtDeserializeError::custom(format!(
+                "expected tuple variant, found block '{}'",
+                block.get_name()
+            ))),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            EnumVariant::Map(_, value) => ValueDeserializer { value }.deserialize_map(visitor),
+            EnumVariant::Block(block) => {
+                BlockDeserializer { block }.deserialize_struct("", fields, visitor)
+            }
+        }
+    }
+}
+
+pub struct BlockDeserializer<'a, 'de> {
+    block: &'a Block<'de>,
+}
+
+impl<'de, 'a> Deserializer<'de> for BlockDeserializer<'a, 'de> {
     type Error = SirtDeserializeError;
 
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if name == BLOCK_TOKEN {
+            return visitor.visit_map(BlockEntriesAccess {
+                block: self.block,
+                entry: 0,
+            });
+        }
+
         visitor.visit_map(BlockMapAccess {
             iter: self.block.fields.iter(),
             value: None,
+            fields: true,
         })
     }
 
@@ -87,25 +318,59 @@ impl<'de, 'a> Deserializer<'de> for BlockDeserializer<'a> {
         self.deserialize_struct("", &[], visitor)
     }
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        unreachable!()
+        if name == VALUE_TOKEN {
+            return visitor.visit_map(TaggedAccess {
+                token: BLOCK_TOKEN,
+                value: Some(self),
+            });
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Visits the fields as a map, so that a block can be
+    /// deserialized into self-describing types, such as a
+    /// `HashMap`, an untagged enum or a flattened struct.
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
     }
 
     serde::forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        bytes byte_buf unit unit_struct seq tuple
         tuple_struct enum identifier ignored_any
     }
 }
 
-struct ValueDeserializer<'a> {
-    value: &'a Value,
+pub(crate) struct ValueDeserializer<'a, 'de> {
+    value: &'a Value<'de>,
 }
 
-impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
+impl<'a, 'de> ValueDeserializer<'a, 'de> {
+    pub(crate) fn new(value: &'a Value<'de>) -> Self {
+        Self { value }
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a, 'de> {
     type Error = SirtDeserializeError;
 
     fn deserialize_any<V>(self, v: V) -> Result<V::Value, Self::Error>
@@ -118,6 +383,10 @@ impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
             Value::Int(_) => self.deserialize_i64(v),
             Value::Float(_) => self.deserialize_f64(v),
             Value::Text(_) => self.deserialize_string(v),
+            Value::Map(_) | Value::Block(_) => self.deserialize_map(v),
+            // buffered as a unit, which can still be read
+            // back as `None`, for untagged and flattened types
+            Value::None => v.visit_unit(),
         }
     }
 
@@ -126,10 +395,8 @@ impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
         V: Visitor<'de>,
     {
         match self.value {
-            Value::Text(s) => visitor.visit_string(s.clone()),
-            other => Err(SirtDeserializeError::custom(format!(
-                "expected string, found {other:?}"
-            ))),
+            Value::Text(text) => visit_text(text, visitor),
+            other => Err(invalid_type("text", other)),
         }
     }
 
@@ -139,9 +406,7 @@ impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
     {
         match self.value {
             Value::Int(num) => visitor.visit_i64(*num),
-            other => Err(SirtDeserializeError::custom(format!(
-                "expected i64, found {other:?}"
-            ))),
+            other => Err(invalid_type("int", other)),
         }
     }
 
@@ -151,9 +416,7 @@ impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
     {
         match self.value {
             Value::Float(f) => visitor.visit_f64(*f),
-            other => Err(SirtDeserializeError::custom(format!(
-                "expected f64, found {other:?}"
-            ))),
+            other => Err(invalid_type("float", other)),
         }
     }
 
@@ -163,9 +426,7 @@ impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
     {
         match self.value {
             Value::Bool(b) => visitor.visit_bool(*b),
-            other => Err(SirtDeserializeError::custom(format!(
-                "expected bool, found {other:?}"
-            ))),
+            other => Err(invalid_type("bool", other)),
         }
     }
 
@@ -174,72 +435,666 @@ impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
         V: Visitor<'de>,
     {
         match self.value {
-            Value::List(list) => visitor.visit_seq(ListAccess { iter: list.iter() }),
-            other => Err(SirtDeserializeError::custom(format!(
-                "expected list, found {other:?}"
-            ))),
+            Value::List(list) => visitor.visit_seq(ListAccess {
+                iter: list.iter(),
+                index: 0,
+            }),
+            other => Err(invalid_type("list", other)),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::None => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::None => visitor.visit_unit(),
+            other => Err(invalid_type("none", other)),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Block(block) => {
+                BlockDeserializer { block }.deserialize_newtype_struct(name, visitor)
+            }
+            Value::Map(_) if name == VALUE_TOKEN => visitor.visit_map(TaggedAccess {
+                token: MAP_TOKEN,
+                value: Some(self),
+            }),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Map(map) => visitor.visit_map(BlockMapAccess {
+                iter: map.iter(),
+                value: None,
+                fields: false,
+            }),
+            Value::Block(block) => BlockDeserializer { block }.deserialize_map(visitor),
+            other => Err(invalid_type("map", other)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Block(block) => {
+                BlockDeserializer { block }.deserialize_struct(name, fields, visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Text(variant) => visitor.visit_enum(variant.as_ref().into_deserializer()),
+            Value::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.iter().next().unwrap();
+                visitor.visit_enum(EnumVariant::Map(variant, value))
+            }
+            Value::Block(block) => visitor.visit_enum(EnumVariant::Block(block)),
+            other => Err(invalid_type("enum variant", other)),
         }
     }
 
     serde::forward_to_deserialize_any! {
         i8 i16 i32 i128 u8 u16 u32 u64 u128 f32 char str
-        bytes byte_buf option unit unit_struct newtype_struct
-        tuple_struct map struct enum identifier ignored_any tuple
+        bytes byte_buf tuple_struct identifier ignored_any tuple
+    }
+}
+
+/// Deserializes a whole document into a struct whose fields
+/// are named after blocks, or into a map from block names.
+struct DocumentDeserializer<'a, 'de> {
+    blocks: &'a [Block<'de>],
+    source_map: &'a SourceMap,
+    /// Fields that no block matches, but that are visited
+    /// anyway, as serde found them to be missing.
+    empty: &'a [&'static str],
+}
+
+/// Returns whether a block name matches a field name when
+/// case and underscores are ignored, so that blocks named
+/// `DevDependency` fill a field named `dev_dependency`.
+pub(crate) fn loosely_matches(field: &str, block: &str) -> bool {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect::<String>()
+    };
+
+    normalize(field) == normalize(block)
+}
+
+impl<'de, 'a> Deserializer<'de> for DocumentDeserializer<'a, 'de> {
+    type Error = SirtDeserializeError;
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // a field without any blocks is only visited once it was
+        // found to be missing, so that it can still have a default,
+        // while blocks that match no field keep their own name,
+        // so that unknown blocks can be rejected
+        let mut groups: IndexMap<&'a str, Vec<usize>> = self
+            .empty
+            .iter()
+            .filter(|field| fields.contains(field))
+            .map(|field| (*field, Vec::new()))
+            .collect();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let name = block.get_name();
+            let field = fields
+                .iter()
+                .find(|field| **field == name)
+                .or_else(|| fields.iter().find(|field| loosely_matches(field, name)));
+
+            groups
+                .entry(field.copied().unwrap_or(name))
+                .or_default()
+                .push(index);
+        }
+
+        visitor.visit_map(DocumentMapAccess {
+            iter: groups.into_iter(),
+            group: None,
+            document: self,
+        })
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_struct("", &[], visitor)
+    }
+
+    /// Reads every block as an element, such as
+    /// into a `Vec` of structs or a [crate::Document].
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        GroupDeserializer {
+            blocks: self.blocks,
+            source_map: self.source_map,
+            name: "",
+            group: (0..self.blocks.len()).collect(),
+        }
+        .deserialize_seq(visitor)
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
     }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct tuple
+        tuple_struct enum identifier ignored_any
+    }
+}
+
+struct DocumentMapAccess<'a, 'de> {
+    iter: indexmap::map::IntoIter<&'a str, Vec<usize>>,
+    group: Option<(&'a str, Vec<usize>)>,
+    document: DocumentDeserializer<'a, 'de>,
+}
+
+impl<'de, 'a> MapAccess<'de> for DocumentMapAccess<'a, 'de> {
+    type Error = SirtDeserializeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, group)) => {
+                self.group = Some((key, group));
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let (name, group) = self
+            .group
+            .take()
+            .ok_or(SirtDeserializeError::custom("MapAccess error"))?;
+
+        seed.deserialize(GroupDeserializer {
+            blocks: self.document.blocks,
+            source_map: self.document.source_map,
+            name,
+            group,
+        })
+    }
+}
+
+/// Deserializes the blocks that fill one field of a document,
+/// which must be a single block unless the field is a sequence
+/// or an option.
+struct GroupDeserializer<'a, 'de> {
+    blocks: &'a [Block<'de>],
+    source_map: &'a SourceMap,
+    /// The name of the field, which is empty when reading
+    /// a whole document as a sequence.
+    name: &'a str,
+    /// The indices of the blocks, which is empty when
+    /// no block matches the field.
+    group: Vec<usize>,
+}
+
+impl<'a, 'de> GroupDeserializer<'a, 'de> {
+    /// Records the name and location of a block in an error.
+    fn locate(&self, index: usize, err: SirtDeserializeError) -> SirtDeserializeError {
+        let span = self
+            .source_map
+            .block(index)
+            .and_then(|spans| locate(spans, err.path()));
+        err.with_span(span)
+    }
+
+    /// Deserializes the only block of the group with `f`.
+    fn single<T>(
+        self,
+        f: impl FnOnce(BlockDeserializer<'a, 'de>) -> Result<T, SirtDeserializeError>,
+    ) -> Result<T, SirtDeserializeError> {
+        let Some(&first) = self.group.first() else {
+            let err =
+                SirtDeserializeError::custom(format!("missing block `{}`", pascal_case(self.name)));
+            return Err(err.prepend(PathSegment::Field(self.name.to_string())));
+        };
+
+        let block = &self.blocks[first];
+        let segment = PathSegment::Block(block.get_name().to_string());
+
+        if let Some(&second) = self.group.get(1) {
+            let err = SirtDeserializeError::custom(format!(
+                "expected one block, found {}",
+                self.group.len()
+            ));
+            return Err(self.locate(second, err).prepend(segment));
+        }
+
+        f(BlockDeserializer { block }).map_err(|err| self.locate(first, err).prepend(segment))
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for GroupDeserializer<'a, 'de> {
+    type Error = SirtDeserializeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.single(|block| block.deserialize_map(visitor))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.single(|block| block.deserialize_struct(name, fields, visitor))
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(GroupAccess {
+            group: &self,
+            index: 0,
+        })
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.group.is_empty() {
+            return visitor.visit_none();
+        }
+
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == VALUE_TOKEN {
+            return self.single(|block| block.deserialize_newtype_struct(name, visitor));
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map enum
+        identifier
+    }
+}
+
+struct GroupAccess<'g, 'a, 'de> {
+    group: &'g GroupDeserializer<'a, 'de>,
+    index: usize,
+}
+
+impl<'de> SeqAccess<'de> for GroupAccess<'_, '_, 'de> {
+    type Error = SirtDeserializeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let Some(&block_index) = self.group.group.get(self.index) else {
+            return Ok(None);
+        };
+
+        let index = self.index;
+        self.index += 1;
+
+        let block = &self.group.blocks[block_index];
+        seed.deserialize(BlockDeserializer { block })
+            .map(Some)
+            .map_err(|err| {
+                self.group
+                    .locate(block_index, err)
+                    .prepend(PathSegment::Index(index))
+                    .prepend(PathSegment::Block(block.get_name().to_string()))
+            })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.group.group.len() - self.index)
+    }
+}
+
+/// Deserializes a single block, recording its name in the path
+/// of any error, and locating the error in the input if possible.
+fn deserialize_block<'de, T>(block: &Block<'de>, spans: Option<&BlockSpans>) -> Result<T, SirtError>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(BlockDeserializer { block }).map_err(|err| {
+        let span = spans.and_then(|spans| locate(spans, err.path()));
+        err.with_span(span)
+            .prepend(PathSegment::Block(block.get_name().to_string()))
+            .into()
+    })
+}
+
+/// Finds the span of the value at `path` inside a block, or
+/// the closest enclosing value that has a span.
+fn locate(spans: &BlockSpans, path: &[PathSegment]) -> Option<Span> {
+    let mut span = spans.span;
+    let mut block = spans;
+
+    for segment in path {
+        let PathSegment::Field(key) = segment else {
+            break;
+        };
+
+        let Some(field) = block.field(key) else {
+            break;
+        };
+
+        span = field.value;
+
+        match &field.block {
+            Some(nested) => block = nested,
+            None => break,
+        }
+    }
+
+    Some(span)
 }
 
 /// Deserialize string representation of data into an
 /// object instance of type `T`.
-pub fn from_str<'de, T>(input: &str) -> Result<T, SirtDeserializeError>
+///
+/// Fields of type `&'de str` or `Cow<'de, str>` marked with
+/// `#[serde(borrow)]` borrow from the input rather than
+/// being copied. Text with escapes can't be borrowed, so
+/// it is an error to deserialize it into a `&'de str`.
+pub fn from_str<'de, T>(input: &'de str) -> Result<T, SirtError>
 where
     T: Deserialize<'de>,
 {
-    let blocks = parse_input(input).map_err(|err| {
-        SirtDeserializeError::custom(format!("failed to parse sirt format: {err:?}"))
-    })?;
+    from_str_with(input, &ParseOptions::default())
+}
 
-    if blocks.is_empty() {
-        return Err(SirtDeserializeError::custom("expected at least one block"));
-    }
+/// Same as [from_str], but parses the input with the
+/// given [ParseOptions].
+pub fn from_str_with<'de, T>(input: &'de str, options: &ParseOptions) -> Result<T, SirtError>
+where
+    T: Deserialize<'de>,
+{
+    let (blocks, source_map) = parse_input_spanned_with(input, options)?;
+
+    let Some(block) = blocks.first() else {
+        return Err(SirtDeserializeError::custom("expected at least one block").into());
+    };
 
-    let block = &blocks[0];
-    let des = BlockDeserializer { block };
-    T::deserialize(des)
+    deserialize_block(block, source_map.block(0))
 }
 
 /// Same as [from_str], but returns the first [Block]
 /// that matches the given name.
-pub fn from_str_named<'de, T>(input: &str, name: &str) -> Result<T, SirtDeserializeError>
+pub fn from_str_named<'de, T>(input: &'de str, name: &str) -> Result<T, SirtError>
 where
     T: Deserialize<'de>,
 {
-    let blocks = parse_input(input).map_err(|err| {
-        SirtDeserializeError::custom(format!("failed to parse sirt format: {err:?}"))
-    })?;
+    from_str_named_with(input, name, &ParseOptions::default())
+}
 
-    let block = blocks.iter().find(|block| block.get_name() == name).ok_or(
-        SirtDeserializeError::custom(format!("couldn't find block with name '{name}'")),
-    )?;
+/// Same as [from_str_named], but parses the input with the
+/// given [ParseOptions].
+pub fn from_str_named_with<'de, T>(
+    input: &'de str,
+    name: &str,
+    options: &ParseOptions,
+) -> Result<T, SirtError>
+where
+    T: Deserialize<'de>,
+{
+    let (blocks, source_map) = parse_input_spanned_with(input, options)?;
 
-    let des = BlockDeserializer { block };
-    T::deserialize(des)
+    let (idx, block) = blocks
+        .iter()
+        .enumerate()
+        .find(|(_, block)| block.get_name() == name)
+        .ok_or(SirtDeserializeError::custom(format!(
+            "couldn't find block with name '{name}'"
+        )))?;
+
+    deserialize_block(block, source_map.block(idx))
+}
+
+/// Deserializes every block of the input into a struct
+/// whose fields are named after the blocks.
+///
+/// A field is filled by the blocks with the same name, or
+/// failing that, with the same name when case and underscores
+/// are ignored. A `Vec` field collects every matching block, an
+/// `Option` field may have no block, and any other field must
+/// have exactly one, unless it has a default. Blocks that match
+/// no field are ignored, unless the struct denies unknown fields.
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     package: Package,
+///     dependency: Vec<Dependency>,
+///     dev_dependency: Vec<Dependency>,
+///     workspace: Option<Package>,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Package {
+///     name: String,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Dependency {
+///     name: String,
+/// }
+///
+/// let input = r#"
+/// Package { name: text("sirt") }
+/// Dependency { name: text("serde") }
+/// Dependency { name: text("indexmap") }
+/// "#;
+///
+/// let config: Config = libsirt::from_str_document(input).unwrap();
+/// assert_eq!(config.package.name, "sirt");
+/// assert_eq!(config.dependency.len(), 2);
+/// assert!(config.dev_dependency.is_empty());
+/// assert!(config.workspace.is_none());
+/// ```
+pub fn from_str_document<'de, T>(input: &'de str) -> Result<T, SirtError>
+where
+    T: Deserialize<'de>,
+{
+    from_str_document_with(input, &ParseOptions::default())
 }
 
-/// Similar to [from_str_named_iter] but returns every instance
+/// Same as [from_str_document], but parses the input with
+/// the given [ParseOptions].
+pub fn from_str_document_with<'de, T>(
+    input: &'de str,
+    options: &ParseOptions,
+) -> Result<T, SirtError>
+where
+    T: Deserialize<'de>,
+{
+    let (blocks, source_map) = parse_input_spanned_with(input, options)?;
+
+    // a field without any blocks is left out, so that it gets its
+    // default, and is only visited as empty when serde finds it to
+    // be missing, so that a `Vec` can be empty without a default
+    let mut empty = Vec::new();
+    loop {
+        let result = T::deserialize(DocumentDeserializer {
+            blocks: &blocks,
+            source_map: &source_map,
+            empty: &empty,
+        });
+
+        match result {
+            Err(err) if err.path().is_empty() => match err.missing_field() {
+                Some(field) if !empty.contains(&field) => empty.push(field),
+                _ => return Err(err.into()),
+            },
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Similar to [from_str_named] but returns every instance
 /// that matches the name as an iterator.
-pub fn from_str_named_iter<T>(
-    input: &str,
-    name: &str,
-) -> Result<impl Iterator<Item = Result<T, SirtDeserializeError>>, SirtDeserializeError>
+pub fn from_str_named_iter<'i, 'n, T>(
+    input: &'i str,
+    name: &'n str,
+) -> Result<impl Iterator<Item = Result<T, SirtError>> + use<'i, 'n, T>, SirtError>
 where
-    T: for<'de> Deserialize<'de>,
+    T: Deserialize<'i>,
 {
-    let blocks = parse_input(input).map_err(|err| {
-        SirtDeserializeError::custom(format!("failed to parse sirt format: '{err:?}'"))
-    })?;
+    from_str_named_iter_with(input, name, &ParseOptions::default())
+}
+
+/// Same as [from_str_named_iter], but parses the input with
+/// the given [ParseOptions].
+pub fn from_str_named_iter_with<'i, 'n, T>(
+    input: &'i str,
+    name: &'n str,
+    options: &ParseOptions,
+) -> Result<impl Iterator<Item = Result<T, SirtError>> + use<'i, 'n, T>, SirtError>
+where
+    T: Deserialize<'i>,
+{
+    let (blocks, source_map) = parse_input_spanned_with(input, options)?;
 
     Ok(blocks
         .into_iter()
-        .filter(move |block| block.get_name() == name)
-        .map(|block| T::deserialize(BlockDeserializer { block: &block })))
+        .zip(source_map)
+        .filter(move |(block, _)| block.get_name() == name)
+        .map(|(block, spans)| deserialize_block(&block, Some(&spans))))
+}
+
+/// Similar to [from_str_named_iter], but reads the blocks from
+/// a [BufRead] one at a time with a [BlockReader], so only the
+/// block being read is held in memory.
+///
+/// Reading stops after the first error in the input, but a
+/// block that can't be deserialized doesn't stop the ones
+/// after it.
+pub fn from_reader_iter<'n, T, R>(
+    reader: R,
+    name: &'n str,
+) -> impl Iterator<Item = Result<T, SirtError>> + use<'n, T, R>
+where
+    T: for<'de> Deserialize<'de>,
+    R: BufRead,
+{
+    from_reader_iter_with(reader, name, &ParseOptions::default())
+}
+
+/// Same as [from_reader_iter], but parses each block with
+/// the given [ParseOptions].
+pub fn from_reader_iter_with<'n, T, R>(
+    reader: R,
+    name: &'n str,
+    options: &ParseOptions,
+) -> impl Iterator<Item = Result<T, SirtError>> + use<'n, T, R>
+where
+    T: for<'de> Deserialize<'de>,
+    R: BufRead,
+{
+    BlockReader::with_options(reader, options)
+        .filter(move |block| !block.as_ref().is_ok_and(|block| block.get_name() != name))
+        .map(|block| deserialize_block(&block?, None))
 }
@@ -0,0 +1,284 @@
+//! A whole Sirt input, as an ordered list of blocks.
+
+use crate::error::ParseError;
+use crate::options::ParseOptions;
+use crate::{Block, parse_input_with};
+
+use std::borrow::Cow;
+
+/// The blocks of a Sirt input, in the order they were written.
+///
+/// Several blocks can share a name, so blocks are looked up
+/// either by their index or by their name, in which case the
+/// first block with the name is used.
+///
+/// ```
+/// use libsirt::{Block, Document, Value};
+///
+/// let mut document = Document::parse(
+///     r#"Dependency { name: text("serde") } Dependency { name: text("indexmap") }"#,
+/// )
+/// .unwrap();
+///
+/// document.push(Block::new("Package").with_field("name", "sirt"));
+/// document.rename("Dependency", "Dep");
+///
+/// assert_eq!(document.iter_named("Dep").count(), 2);
+/// assert_eq!(
+///     document.get_named("Package").unwrap().get("name"),
+///     Some(&Value::Text("sirt".into()))
+/// );
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Document<'a> {
+    blocks: Vec<Block<'a>>,
+}
+
+impl<'a> Document<'a> {
+    /// Creates a document without any blocks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a document with the default [ParseOptions],
+    /// same as [crate::parse_input].
+    pub fn parse(input: &'a str) -> Result<Self, ParseError> {
+        Self::parse_with(input, &ParseOptions::default())
+    }
+
+    /// Same as [Document::parse], but with the given [ParseOptions].
+    pub fn parse_with(input: &'a str, options: &ParseOptions) -> Result<Self, ParseError> {
+        parse_input_with(input, options).map(Self::from)
+    }
+
+    /// Returns the number of blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Whether the document has no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns the blocks, in order.
+    pub fn get_blocks(&self) -> &[Block<'a>] {
+        &self.blocks
+    }
+
+    /// Returns the block at `index`.
+    pub fn get(&self, index: usize) -> Option<&Block<'a>> {
+        self.blocks.get(index)
+    }
+
+    /// Returns the block at `index` so that it can be edited.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Block<'a>> {
+        self.blocks.get_mut(index)
+    }
+
+    /// Returns the first block named `name`.
+    pub fn get_named(&self, name: &str) -> Option<&Block<'a>> {
+        self.blocks.iter().find(|block| block.get_name() == name)
+    }
+
+    /// Returns the first block named `name` so
+    /// that it can be edited.
+    pub fn get_named_mut(&mut self, name: &str) -> Option<&mut Block<'a>> {
+        self.blocks
+            .iter_mut()
+            .find(|block| block.get_name() == name)
+    }
+
+    /// Returns an iterator over the blocks, in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Block<'a>> {
+        self.blocks.iter()
+    }
+
+    /// Returns an iterator that allows editing each block.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Block<'a>> {
+        self.blocks.iter_mut()
+    }
+
+    /// Returns an iterator over every block named `name`, in order.
+    pub fn iter_named<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s Block<'a>> {
+        self.blocks
+            .iter()
+            .filter(move |block| block.get_name() == name)
+    }
+
+    /// Same as [Document::iter_named], but allows editing each block.
+    pub fn iter_named_mut<'s>(
+        &'s mut self,
+        name: &'s str,
+    ) -> impl Iterator<Item = &'s mut Block<'a>> {
+        self.blocks
+            .iter_mut()
+            .filter(move |block| block.get_name() == name)
+    }
+
+    /// Adds a block after every other block.
+    pub fn push(&mut self, block: Block<'a>) {
+        self.blocks.push(block);
+    }
+
+    /// Inserts a block at `index`, moving the
+    /// blocks after it along by one.
+    ///
+    /// # Panics
+    /// If `index` is greater than the number of blocks.
+    pub fn insert(&mut self, index: usize, block: Block<'a>) {
+        self.blocks.insert(index, block);
+    }
+
+    /// Removes and returns the block at `index`,
+    /// keeping the order of the other blocks.
+    pub fn remove(&mut self, index: usize) -> Option<Block<'a>> {
+        (index < self.blocks.len()).then(|| self.blocks.remove(index))
+    }
+
+    /// Removes and returns every block named `name`,
+    /// keeping the order of the other blocks.
+    pub fn remove_named(&mut self, name: &str) -> Vec<Block<'a>> {
+        let (removed, kept) = std::mem::take(&mut self.blocks)
+            .into_iter()
+            .partition(|block| block.get_name() == name);
+        self.blocks = kept;
+        removed
+    }
+
+    /// Renames every block named `from` to `to`, returning
+    /// how many blocks were renamed.
+    pub fn rename(&mut self, from: &str, to: impl Into<Cow<'a, str>>) -> usize {
+        let to = to.into();
+        let mut renamed = 0;
+
+        for block in self.iter_named_mut(from) {
+            block.set_name(to.clone());
+            renamed += 1;
+        }
+
+        renamed
+    }
+
+    /// Returns the blocks, consuming the document.
+    pub fn into_blocks(self) -> Vec<Block<'a>> {
+        self.blocks
+    }
+
+    /// Copies any borrowed text, so the document no
+    /// longer borrows from the input.
+    pub fn into_owned(self) -> Document<'static> {
+        Document {
+            blocks: self.blocks.into_iter().map(Block::into_owned).collect(),
+        }
+    }
+}
+
+impl<'a> From<Vec<Block<'a>>> for Document<'a> {
+    fn from(blocks: Vec<Block<'a>>) -> Self {
+        Self { blocks }
+    }
+}
+
+impl<'a> FromIterator<Block<'a>> for Document<'a> {
+    fn from_iter<I: IntoIterator<Item = Block<'a>>>(iter: I) -> Self {
+        Self {
+            blocks: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> Extend<Block<'a>> for Document<'a> {
+    fn extend<I: IntoIterator<Item = Block<'a>>>(&mut self, iter: I) {
+        self.blocks.extend(iter);
+    }
+}
+
+impl<'a> IntoIterator for Document<'a> {
+    type Item = Block<'a>;
+    type IntoIter = std::vec::IntoIter<Block<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.blocks.into_iter()
+    }
+}
+
+impl<'s, 'a> IntoIterator for &'s Document<'a> {
+    type Item = &'s Block<'a>;
+    type IntoIter = std::slice::Iter<'s, Block<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.blocks.iter()
+    }
+}
+
+impl<'s, 'a> IntoIterator for &'s mut Document<'a> {
+    type Item = &'s mut Block<'a>;
+    type IntoIter = std::slice::IterMut<'s, Block<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.blocks.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    fn names(document: &Document) -> Vec<String> {
+        document
+            .iter()
+            .map(|block| block.get_name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_lookup() {
+        let document = Document::parse("A { x: int(1) } B {} A { x: int(2) }").unwrap();
+
+        assert_eq!(document.len(), 3);
+        assert_eq!(document.get(1).unwrap().get_name(), "B");
+        assert!(document.get(3).is_none());
+        assert_eq!(
+            document.get_named("A").unwrap().get("x"),
+            Some(&Value::Int(1))
+        );
+        assert!(document.get_named("C").is_none());
+
+        let xs: Vec<_> = document
+            .iter_named("A")
+            .map(|block| block.get("x").unwrap())
+            .collect();
+        assert_eq!(xs, [&Value::Int(1), &Value::Int(2)]);
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut document = Document::parse("A {} B {} A {} C {}").unwrap();
+
+        document.insert(0, Block::new("First"));
+        document.push(Block::new("Last"));
+        assert_eq!(names(&document), ["First", "A", "B", "A", "C", "Last"]);
+
+        assert_eq!(document.rename("A", "Renamed"), 2);
+        assert_eq!(document.rename("Missing", "Renamed"), 0);
+        assert_eq!(
+            names(&document),
+            ["First", "Renamed", "B", "Renamed", "C", "Last"]
+        );
+
+        let removed = document.remove_named("Renamed");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(names(&document), ["First", "B", "C", "Last"]);
+
+        assert_eq!(document.remove(1).unwrap().get_name(), "B");
+        assert!(document.remove(3).is_none());
+        assert_eq!(names(&document), ["First", "C", "Last"]);
+
+        for block in &mut document {
+            block.insert_field("seen", true);
+        }
+        assert!(document.iter().all(|block| block.get("seen").is_some()));
+    }
+}
@@ -1,76 +1,506 @@
+use crate::span::Span;
 use serde::{de, ser};
-use std::error::Error;
+use std::error::Error as StdError;
+use std::ops::Range;
 
-/// An error representing possible
-/// values that the parser could
-/// return.
+/// The kind of a [ParseError].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input does not follow the syntax of Sirt.
+    Syntax,
+    /// An int is out of range.
+    Int,
+    /// A float is out of range.
+    Float,
+    /// A bool has a value that is not supported.
+    Bool,
+    /// Text contains an invalid escape sequence.
+    Text,
+    /// A key appears more than once in the same block or map.
+    DuplicateKey,
+    /// Blocks, lists or maps are nested deeper than the
+    /// limit set in [crate::options::ParseOptions].
+    DepthLimit,
+    /// The input is larger than the limit set in
+    /// [crate::options::ParseOptions].
+    InputSizeLimit,
+    /// The input has more values than the limit set in
+    /// [crate::options::ParseOptions].
+    ValueCountLimit,
+    /// Text is longer than the limit set in
+    /// [crate::options::ParseOptions].
+    TextLengthLimit,
+}
+
+/// An error returned by the parser, along with the
+/// location in the input that caused it.
 #[derive(Debug, Clone, PartialEq)]
-pub enum ParseError<'a> {
-    Input(String),
-    Block,
-    Value,
-    Field,
-    Int(String),
-    Float(String),
-    Bool(String),
-    Other(&'a str),
+pub struct ParseError {
+    kind: ParseErrorKind,
+    message: String,
+    span: Span,
+    expected: Vec<String>,
+    snippet: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(
+        kind: ParseErrorKind,
+        message: impl Into<String>,
+        input: &str,
+        range: Range<usize>,
+    ) -> Self {
+        let line_start = input[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[range.start..]
+            .find('\n')
+            .map_or(input.len(), |i| range.start + i);
+        let line = input[..range.start].matches('\n').count() + 1;
+        let column = input[line_start..range.start].chars().count() + 1;
+
+        let source = input[line_start..line_end].trim_end_matches('\r');
+        let carets = input[range.start..range.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let marker = format!("{}{}", " ".repeat(column - 1), "^".repeat(carets));
+        let snippet = render_snippet(line, source, &marker);
+
+        Self {
+            kind,
+            message: message.into(),
+            span: Span {
+                start: range.start,
+                end: range.end,
+                line,
+                column,
+            },
+            expected: Vec::new(),
+            snippet,
+        }
+    }
+
+    pub(crate) fn with_expected(mut self, expected: Vec<String>) -> Self {
+        self.expected = expected;
+        self
+    }
+
+    /// Moves an error found in part of a larger input to its
+    /// location in the whole input, where the part starts
+    /// `bytes` into the input, on the given line after `columns`
+    /// characters. The snippet shows `prefix` in front of the
+    /// part, which is the text of the line before it, or the
+    /// end of that text if the line is long.
+    pub(crate) fn offset(
+        mut self,
+        bytes: usize,
+        line: usize,
+        columns: usize,
+        prefix: &str,
+    ) -> Self {
+        let mut lines = self.snippet.lines().skip(1);
+        let source = lines.next().and_then(|l| l.split_once(" | ")).map(|l| l.1);
+        let marker = lines.next().and_then(|l| l.split_once(" | ")).map(|l| l.1);
+        let (mut source, mut marker) = (
+            source.unwrap_or_default().to_string(),
+            marker.unwrap_or_default().to_string(),
+        );
+
+        if self.span.line == 1 {
+            self.span.column += columns;
+            source.insert_str(0, prefix);
+            marker.insert_str(0, &" ".repeat(prefix.chars().count()));
+        }
+        self.span.line += line - 1;
+        self.span.start += bytes;
+        self.span.end += bytes;
+        self.snippet = render_snippet(self.span.line, &source, &marker);
+
+        self
+    }
+
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Returns the description of the error, without its location.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the location in the input that caused the error.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the line of the error, starting at 1.
+    pub fn line(&self) -> usize {
+        self.span.line
+    }
+
+    /// Returns the column of the error in characters, starting at 1.
+    pub fn column(&self) -> usize {
+        self.span.column
+    }
+
+    /// Returns the byte range of the input that caused the error.
+    pub fn range(&self) -> Range<usize> {
+        self.span.range()
+    }
+
+    /// Returns the tokens that the parser expected to find
+    /// instead, if any.
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+
+    /// Returns the line of the input that caused the error,
+    /// with a caret pointing at the error.
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+}
+
+/// Renders the line of an error with a marker under it.
+fn render_snippet(line: usize, source: &str, marker: &str) -> String {
+    let gutter = " ".repeat(line.to_string().len());
+    format!("{gutter} |\n{line} | {source}\n{gutter} | {marker}")
 }
 
-impl<'a> Error for ParseError<'a> {}
+impl StdError for ParseError {}
 
-impl<'a> std::fmt::Display for ParseError<'a> {
+impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{}",
-            match self {
-                ParseError::Input(i) => i,
-                ParseError::Block => "Block",
-                ParseError::Value => "Value",
-                ParseError::Field => "Field",
-                ParseError::Int(msg) | ParseError::Float(msg) | ParseError::Bool(msg) => msg,
-                ParseError::Other(reason) => reason,
-            }
-        )
+            "{} at line {}, column {}\n{}",
+            self.message, self.span.line, self.span.column, self.snippet
+        )?;
+
+        if !self.expected.is_empty() {
+            write!(f, "\nexpected {}", self.expected.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Any error that can be returned by libsirt.
+///
+/// The underlying error is available through
+/// [std::error::Error::source], or by matching
+/// on the variants. Its message is not repeated
+/// when the error is displayed, so a report of
+/// the error chain shows each message once.
+#[derive(Debug)]
+pub enum Error {
+    /// The input could not be read.
+    Io(std::io::Error),
+    /// The input could not be parsed.
+    Parse(ParseError),
+    /// The parsed input could not be deserialized
+    /// into the requested type.
+    Deserialize(SirtDeserializeError),
+    /// A value could not be serialized.
+    Serialize(SirtSerializeError),
+    /// A field or value did not have the requested type.
+    Value(ValueError),
+}
+
+impl Error {
+    /// Returns the path to the value that caused the error,
+    /// such as `Package.authors[1]`, if there is one.
+    pub fn path(&self) -> Option<&[PathSegment]> {
+        match self {
+            Error::Io(_) | Error::Parse(_) => None,
+            Error::Deserialize(err) => Some(err.path()),
+            Error::Serialize(err) => Some(err.path()),
+            Error::Value(err) => Some(err.path()),
+        }
+    }
+
+    /// Returns the location in the input that caused
+    /// the error, if it is known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Parse(err) => Some(err.span()),
+            Error::Deserialize(err) => err.span(),
+            Error::Io(_) | Error::Serialize(_) | Error::Value(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(_) => write!(f, "failed to read input"),
+            Error::Parse(_) => write!(f, "failed to parse input"),
+            Error::Deserialize(_) => write!(f, "failed to deserialize value"),
+            Error::Serialize(_) => write!(f, "failed to serialize value"),
+            Error::Value(_) => write!(f, "failed to convert value"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Parse(err) => Some(err),
+            Error::Deserialize(err) => Some(err),
+            Error::Serialize(err) => Some(err),
+            Error::Value(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+impl From<SirtDeserializeError> for Error {
+    fn from(err: SirtDeserializeError) -> Self {
+        Error::Deserialize(err)
+    }
+}
+
+impl From<SirtSerializeError> for Error {
+    fn from(err: SirtSerializeError) -> Self {
+        Error::Serialize(err)
     }
 }
 
+impl From<ValueError> for Error {
+    fn from(err: ValueError) -> Self {
+        Error::Value(err)
+    }
+}
+
+/// A step in the path to a value, which is shown
+/// in front of an error message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A block with the given name.
+    Block(String),
+    /// A field of a block.
+    Field(String),
+    /// An element of a list.
+    Index(usize),
+    /// An entry of a map.
+    Key(String),
+}
+
+fn write_path(f: &mut std::fmt::Formatter<'_>, path: &[PathSegment]) -> std::fmt::Result {
+    for (i, segment) in path.iter().enumerate() {
+        match segment {
+            PathSegment::Block(name) => write!(f, "{name}")?,
+            PathSegment::Field(name) if i == 0 => write!(f, "{name}")?,
+            PathSegment::Field(name) => write!(f, ".{name}")?,
+            PathSegment::Index(index) => write!(f, "[{index}]")?,
+            PathSegment::Key(key) => write!(f, "[{key:?}]")?,
+        }
+    }
+
+    if !path.is_empty() {
+        write!(f, ": ")?;
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
-pub struct SirtDeserializeError(String);
+pub struct SirtDeserializeError {
+    message: String,
+    path: Vec<PathSegment>,
+    span: Option<Span>,
+    /// The field that serde found to be missing, if that
+    /// is what caused the error.
+    missing_field: Option<&'static str>,
+}
+
+impl SirtDeserializeError {
+    /// Returns the description of the error, without its path.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the path to the value that caused the error.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// Returns the location of the value that caused the
+    /// error, if it is known.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub(crate) fn prepend(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    pub(crate) fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub(crate) fn missing_field(&self) -> Option<&'static str> {
+        self.missing_field
+    }
+}
 
 impl de::Error for SirtDeserializeError {
     fn custom<T>(msg: T) -> Self
     where
         T: std::fmt::Display,
     {
-        Self(msg.to_string())
+        Self {
+            message: msg.to_string(),
+            path: Vec::new(),
+            span: None,
+            missing_field: None,
+        }
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Self {
+            missing_field: Some(field),
+            ..Self::custom(format_args!("missing field `{field}`"))
+        }
     }
 }
 
 impl std::fmt::Display for SirtDeserializeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write_path(f, &self.path)?;
+        write!(f, "{}", self.message)?;
+
+        if let Some(span) = self.span {
+            write!(f, " at line {}, column {}", span.line, span.column)?;
+        }
+
+        Ok(())
     }
 }
 
-impl Error for SirtDeserializeError {}
+impl StdError for SirtDeserializeError {}
 
 #[derive(Debug, Clone)]
-pub struct SirtSerializeError(String);
+pub struct SirtSerializeError {
+    message: String,
+    path: Vec<PathSegment>,
+}
+
+impl SirtSerializeError {
+    /// Returns the description of the error, without its path.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the path to the value that caused the error.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    pub(crate) fn prepend(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+}
 
 impl ser::Error for SirtSerializeError {
     fn custom<T>(msg: T) -> Self
     where
         T: std::fmt::Display,
     {
-        Self(msg.to_string())
+        Self {
+            message: msg.to_string(),
+            path: Vec::new(),
+        }
     }
 }
 
 impl std::fmt::Display for SirtSerializeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write_path(f, &self.path)?;
+        write!(f, "{}", self.message)
+    }
+}
+
+impl StdError for SirtSerializeError {}
+
+/// The kind of a [ValueError].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueErrorKind {
+    /// A block does not have the field with the given name.
+    MissingField(String),
+    /// A value has a different type than the one requested,
+    /// named as in Sirt, such as `int` or `text`.
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+/// An error returned by the typed accessors of [crate::Block]
+/// and by the conversions from [crate::Value].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueError {
+    kind: ValueErrorKind,
+    path: Vec<PathSegment>,
+}
+
+impl ValueError {
+    pub(crate) fn missing_field(field: &str) -> Self {
+        Self {
+            kind: ValueErrorKind::MissingField(field.to_string()),
+            path: Vec::new(),
+        }
+    }
+
+    pub(crate) fn type_mismatch(expected: &'static str, found: &'static str) -> Self {
+        Self {
+            kind: ValueErrorKind::TypeMismatch { expected, found },
+            path: Vec::new(),
+        }
+    }
+
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> &ValueErrorKind {
+        &self.kind
+    }
+
+    /// Returns the path to the value that caused the error.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    pub(crate) fn prepend(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_path(f, &self.path)?;
+
+        match &self.kind {
+            ValueErrorKind::MissingField(field) => write!(f, "missing field `{field}`"),
+            ValueErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+        }
     }
 }
 
-impl Error for SirtSerializeError {}
+impl StdError for ValueError {}
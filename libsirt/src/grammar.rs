@@ -0,0 +1,550 @@
+//! The pest grammar that Sirt was first parsed with, kept
+//! behind the `pest` feature to compare against the
+//! hand-written parser behind [crate::parse_input].
+//!
+//! Both parsers accept the same input and return the same
+//! blocks, locations and errors, except that a syntax error
+//! may be reported at a different position.
+
+use crate::error::{ParseError, ParseErrorKind};
+use crate::literal::{insert_key, parse_bool, parse_float, parse_int, parse_text};
+use crate::options::{DuplicateKeys, ParseOptions};
+use crate::span::{BlockSpans, FieldSpans, LineIndex, SourceMap};
+use crate::types::List;
+use crate::{Block, Value};
+
+use indexmap::IndexMap;
+use pest::error::{ErrorVariant, InputLocation};
+use pest::iterators::{Pair, Pairs};
+use pest::{Parser, Span};
+use pest_derive::Parser;
+use std::borrow::Cow;
+
+#[derive(Parser)]
+#[grammar = "../grammar/grammar.pest"]
+pub(crate) struct SirtParser;
+
+/// Same as [crate::parse_input], but using the pest grammar.
+pub fn parse_input(input: &str) -> Result<Vec<Block<'_>>, ParseError> {
+    parse_input_with(input, &ParseOptions::default())
+}
+
+/// Same as [crate::parse_input_with], but using the pest grammar.
+pub fn parse_input_with<'i>(
+    input: &'i str,
+    options: &ParseOptions,
+) -> Result<Vec<Block<'i>>, ParseError> {
+    check_input(input, options)?;
+
+    let mut state = State::new(options);
+    let mut blocks = Vec::new();
+    let mut pairs = parse_pairs(input)?;
+
+    if let Some(pairs) = pairs.next() {
+        for pair in pairs.into_inner() {
+            if pair.as_rule() == Rule::block {
+                blocks.push(parse_block(pair.clone(), &mut state)?);
+                state.count(&pair)?;
+            }
+        }
+    }
+
+    Ok(blocks)
+}
+
+/// Same as [crate::parse_input_spanned], but using the pest grammar.
+pub fn parse_input_spanned(input: &str) -> Result<(Vec<Block<'_>>, SourceMap), ParseError> {
+    parse_input_spanned_with(input, &ParseOptions::default())
+}
+
+/// Same as [crate::parse_input_spanned_with], but using the pest grammar.
+pub fn parse_input_spanned_with<'i>(
+    input: &'i str,
+    options: &ParseOptions,
+) -> Result<(Vec<Block<'i>>, SourceMap), ParseError> {
+    check_input(input, options)?;
+
+    let mut state = State::new(options);
+    let mut blocks = Vec::new();
+    let mut source_map = SourceMap::default();
+    let lines = LineIndex::new(input);
+    let mut pairs = parse_pairs(input)?;
+
+    if let Some(pairs) = pairs.next() {
+        for pair in pairs.into_inner() {
+            if pair.as_rule() == Rule::block {
+                source_map.blocks.push(block_spans(&pair, &lines, options));
+                blocks.push(parse_block(pair.clone(), &mut state)?);
+                state.count(&pair)?;
+            }
+        }
+    }
+
+    Ok((blocks, source_map))
+}
+
+/// State of a single parse, counting the values
+/// against the limits of the options.
+struct State<'o> {
+    options: &'o ParseOptions,
+    values: usize,
+}
+
+impl State<'_> {
+    fn new(options: &ParseOptions) -> State<'_> {
+        State { options, values: 0 }
+    }
+
+    /// Counts a block or value that has been parsed.
+    fn count(&mut self, pair: &Pair<'_, Rule>) -> Result<(), ParseError> {
+        self.values += 1;
+
+        let span = pair.as_span();
+        self.options
+            .check_values(self.values, span.get_input(), span.start()..span.end())
+    }
+}
+
+/// Checks the limits that must hold before the input is
+/// given to the grammar.
+fn check_input(input: &str, options: &ParseOptions) -> Result<(), ParseError> {
+    options.check_input_size(input)?;
+    check_depth(input, options)
+}
+
+/// Finds blocks, lists and maps that are nested deeper than
+/// the options allow, without building anything.
+///
+/// The grammar recurses for every nested value, so this has
+/// to run before it to keep deeply nested input from
+/// overflowing the stack.
+fn check_depth(input: &str, options: &ParseOptions) -> Result<(), ParseError> {
+    if options.get_max_depth().is_none() {
+        return Ok(());
+    }
+
+    let bytes = input.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+
+    let mut depth = 0usize;
+    // whether the `(` of a value that is not a list or map is open
+    let mut in_value = false;
+    // start of the last word, if it opens a list or map
+    let mut container: Option<usize> = None;
+    let mut word_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+
+        if b == b'#' {
+            i = input[i..].find('\n').map_or(bytes.len(), |n| i + n + 1);
+            continue;
+        }
+
+        if b == b'"' {
+            i = skip_quoted(bytes, i + 1);
+            container = None;
+            continue;
+        }
+
+        if b == b'r' && (i == 0 || !is_ident(bytes[i - 1])) {
+            let hashes = bytes[i + 1..].iter().take_while(|b| **b == b'#').count();
+            if bytes.get(i + 1 + hashes) == Some(&b'"') {
+                let close = format!("\"{}", "#".repeat(hashes));
+                let start = i + hashes + 2;
+                i = input[start..]
+                    .find(&close)
+                    .map_or(bytes.len(), |n| start + n + close.len());
+                container = None;
+                continue;
+            }
+        }
+
+        if is_ident(b) {
+            let start = i;
+            while i < bytes.len() && is_ident(bytes[i]) {
+                i += 1;
+            }
+
+            word_start = start;
+            container = matches!(&input[start..i], "list" | "map").then_some(start);
+            continue;
+        }
+
+        let opened = match b {
+            b'{' => Some(word_start),
+            b'(' if container.is_some() => container,
+            b'(' => {
+                in_value = true;
+                None
+            }
+            b')' if in_value => {
+                in_value = false;
+                None
+            }
+            b')' | b'}' => {
+                depth = depth.saturating_sub(1);
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(start) = opened {
+            depth += 1;
+            options.check_depth(depth, input, start..i + 1)?;
+        }
+
+        if !b.is_ascii_whitespace() {
+            container = None;
+        }
+
+        i += 1;
+    }
+
+    Ok(())
+}
+
+/// Returns the index after the end of a quoted string,
+/// starting after its opening quote.
+fn skip_quoted(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+
+    bytes.len()
+}
+
+fn block_spans(pair: &Pair<'_, Rule>, lines: &LineIndex, options: &ParseOptions) -> BlockSpans {
+    let span = |pair: &Pair<'_, Rule>| {
+        let span = pair.as_span();
+        lines.span(span.start()..span.end())
+    };
+
+    let mut inner = pair.clone().into_inner();
+    let name = inner.next().map(|name| span(&name)).unwrap_or(span(pair));
+    let mut fields = IndexMap::new();
+
+    for field in inner {
+        let mut parts = field.into_inner();
+        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+            let block = (value.as_rule() == Rule::block)
+                .then(|| Box::new(block_spans(&value, lines, options)));
+
+            let spans = FieldSpans {
+                key: span(&key),
+                value: span(&value),
+                block,
+            };
+
+            // duplicates are reported by `parse_block`
+            if options.get_duplicate_keys() == DuplicateKeys::FirstWins {
+                fields.entry(key.as_str().to_string()).or_insert(spans);
+            } else {
+                fields.insert(key.as_str().to_string(), spans);
+            }
+        }
+    }
+
+    BlockSpans {
+        span: span(pair),
+        name,
+        fields,
+    }
+}
+
+/// Runs the grammar over the input, turning a pest error
+/// into a located [ParseError].
+fn parse_pairs(input: &str) -> Result<Pairs<'_, Rule>, ParseError> {
+    SirtParser::parse(Rule::input, input).map_err(|err| {
+        let range = match err.location {
+            InputLocation::Pos(pos) => pos..pos,
+            InputLocation::Span((start, end)) => start..end,
+        };
+
+        let (message, expected) = match err.variant {
+            ErrorVariant::ParsingError { positives, .. } => {
+                let mut expected: Vec<String> = positives
+                    .into_iter()
+                    .map(describe)
+                    .map(String::from)
+                    .collect();
+                expected.dedup();
+                ("unexpected input".to_string(), expected)
+            }
+            ErrorVariant::CustomError { message } => (message, Vec::new()),
+        };
+
+        ParseError::new(ParseErrorKind::Syntax, message, input, range).with_expected(expected)
+    })
+}
+
+/// A readable name of a rule, for the tokens that
+/// were expected by the parser.
+fn describe(rule: Rule) -> &'static str {
+    match rule {
+        Rule::EOI => "end of input",
+        Rule::block => "block",
+        Rule::field => "field",
+        Rule::ident => "identifier",
+        Rule::trailing_comma => "','",
+        Rule::text => "text(...)",
+        Rule::int => "int(...)",
+        Rule::float => "float(...)",
+        Rule::bool => "bool(...)",
+        Rule::list => "list(...)",
+        Rule::map => "map(...)",
+        Rule::none => "none",
+        Rule::value_list => "value",
+        Rule::entry_list | Rule::entry => "map entry",
+        Rule::string | Rule::quoted_string | Rule::raw_string => "string",
+        Rule::escape => "escape sequence",
+        Rule::number => "number",
+        Rule::decimal | Rule::exponent => "decimal number",
+        _ => "token",
+    }
+}
+
+/// Creates an error located at the given pair.
+fn error_at(pair: &Pair<'_, Rule>, kind: ParseErrorKind, message: String) -> ParseError {
+    let span = pair.as_span();
+    ParseError::new(kind, message, span.get_input(), span.start()..span.end())
+}
+
+/// Returns the next inner pair, which is guaranteed to
+/// exist by the grammar.
+fn next_inner<'i>(
+    pairs: &mut Pairs<'i, Rule>,
+    parent: Span<'i>,
+) -> Result<Pair<'i, Rule>, ParseError> {
+    pairs.next().ok_or_else(|| {
+        ParseError::new(
+            ParseErrorKind::Syntax,
+            "malformed input",
+            parent.get_input(),
+            parent.start()..parent.end(),
+        )
+    })
+}
+
+/// Fails on a trailing comma, unless the options allow it.
+fn check_trailing_comma(pair: &Pair<'_, Rule>, state: &State) -> Result<(), ParseError> {
+    if state.options.get_trailing_commas() {
+        return Ok(());
+    }
+
+    Err(error_at(
+        pair,
+        ParseErrorKind::Syntax,
+        "trailing comma".to_string(),
+    ))
+}
+
+fn range(pair: &Pair<'_, Rule>) -> std::ops::Range<usize> {
+    pair.as_span().start()..pair.as_span().end()
+}
+
+fn parse_block<'i>(pair: Pair<'i, Rule>, state: &mut State) -> Result<Block<'i>, ParseError> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let mut fields = IndexMap::new();
+
+    let name = Cow::Borrowed(next_inner(&mut inner, span)?.as_str());
+
+    for field in inner {
+        let span = field.as_span();
+        let mut parts = field.into_inner();
+        let key = next_inner(&mut parts, span)?;
+        let value = parse_value(next_inner(&mut parts, span)?, state)?;
+        insert_key(
+            &mut fields,
+            Cow::Borrowed(key.as_str()),
+            value,
+            state.options,
+            span.get_input(),
+            range(&key),
+            "field",
+        )?;
+    }
+
+    Ok(Block { name, fields })
+}
+
+fn parse_value<'i>(pair: Pair<'i, Rule>, state: &mut State) -> Result<Value<'i>, ParseError> {
+    let span = pair.as_span();
+    let input = span.get_input();
+
+    let value = match pair.as_rule() {
+        Rule::text => Value::Text(parse_string(
+            next_inner(&mut pair.clone().into_inner(), span)?,
+            state,
+        )?),
+        Rule::int | Rule::float | Rule::bool => {
+            let literal = next_inner(&mut pair.clone().into_inner(), span)?;
+            match pair.as_rule() {
+                Rule::int => parse_int(input, range(&literal))?,
+                Rule::float => parse_float(input, range(&literal))?,
+                _ => parse_bool(input, range(&literal), state.options)?,
+            }
+        }
+        Rule::list => Value::List(parse_list(pair.clone(), state)?),
+        Rule::map => Value::Map(parse_map(pair.clone(), state)?),
+        Rule::block => Value::Block(parse_block(pair.clone(), state)?),
+        Rule::none => Value::None,
+        _ => unreachable!(),
+    };
+
+    state.count(&pair)?;
+    Ok(value)
+}
+
+fn parse_list<'i>(pair: Pair<'i, Rule>, state: &mut State) -> Result<List<Value<'i>>, ParseError> {
+    let mut list = List::with_capacity(1);
+
+    for inner in pair.into_inner() {
+        if inner.as_rule() == Rule::value_list {
+            for v in inner.into_inner() {
+                if v.as_rule() == Rule::trailing_comma {
+                    check_trailing_comma(&v, state)?;
+                } else {
+                    list.push(parse_value(v, state)?);
+                }
+            }
+        }
+    }
+
+    Ok(list)
+}
+
+fn parse_map<'i>(
+    pair: Pair<'i, Rule>,
+    state: &mut State,
+) -> Result<IndexMap<Cow<'i, str>, Value<'i>>, ParseError> {
+    let mut map = IndexMap::new();
+
+    for inner in pair.into_inner() {
+        if inner.as_rule() == Rule::entry_list {
+            for entry in inner.into_inner() {
+                if entry.as_rule() == Rule::trailing_comma {
+                    check_trailing_comma(&entry, state)?;
+                    continue;
+                }
+
+                let span = entry.as_span();
+                let mut parts = entry.into_inner();
+                let key_pair = next_inner(&mut parts, span)?;
+                let key = parse_string(key_pair.clone(), state)?;
+                let value = parse_value(next_inner(&mut parts, span)?, state)?;
+                insert_key(
+                    &mut map,
+                    key,
+                    value,
+                    state.options,
+                    span.get_input(),
+                    range(&key_pair),
+                    "key",
+                )?;
+            }
+        }
+    }
+
+    Ok(map)
+}
+
+fn parse_string<'i>(pair: Pair<'i, Rule>, state: &State) -> Result<Cow<'i, str>, ParseError> {
+    let span = pair.as_span();
+    let inner = next_inner(&mut pair.into_inner(), span)?;
+    parse_text(span.get_input(), range(&inner), state.options)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fmt::Debug;
+
+    /// Checks that both parsers returned the same result for
+    /// `input`, allowing a syntax error to be reported elsewhere.
+    ///
+    /// The tests of [crate::parser] run every input through this.
+    pub(crate) fn assert_same<T>(
+        input: &str,
+        expected: &Result<T, ParseError>,
+        actual: &Result<T, ParseError>,
+    ) where
+        T: Debug + PartialEq,
+    {
+        match (expected, actual) {
+            (Ok(expected), Ok(actual)) => assert_eq!(expected, actual, "{input}"),
+            (Err(expected), Err(actual)) => {
+                assert_eq!(expected.kind(), actual.kind(), "{input}");
+                if expected.kind() != ParseErrorKind::Syntax {
+                    assert_eq!(expected, actual, "{input}");
+                }
+            }
+            (expected, actual) => panic!("{input}: {expected:?} != {actual:?}"),
+        }
+    }
+
+    /// Parses the input with both parsers, which must agree.
+    fn compare(input: &str, options: &ParseOptions) {
+        assert_same(
+            input,
+            &crate::parse_input_spanned_with(input, options),
+            &parse_input_spanned_with(input, options),
+        );
+    }
+
+    #[test]
+    fn test_same_as_parser() {
+        let strict = ParseOptions::new();
+        let lenient = ParseOptions::new()
+            .lenient_bools(true)
+            .trailing_commas(true)
+            .duplicate_keys(DuplicateKeys::FirstWins);
+        let limited = ParseOptions::new()
+            .max_depth(2)
+            .max_values(4)
+            .max_text_length(3);
+
+        let inputs = [
+            "",
+            "# only a comment\n",
+            "App {} # a comment without a newline",
+            "App {}\nApp { a: none }",
+            "App { a: none { b: none } c: list { } }",
+            "App { a: none {",
+            "App {\n    name: text(\"sirt\") # comment\n    port: int(-80)\n}",
+            r##"App { a: text("say \"hi\"\n\u{1F980}") b: text(r#"C:\"#) }"##,
+            "App { a: float(1.5) b: float(-2e10) c: float(inf) d: float(1.) }",
+            "App { a: bool(yes) b: bool(on) c: bool(1) d: bool(maybe) }",
+            r#"App { l: list(int(1), list(), map("k": none)) m: map("a": text("b"),) }"#,
+            "App { w: Window { size: Size { w: int(800) h: int(600) } } }",
+            "App { a: int(1) a: int(2) }",
+            r#"App { m: map("a": none, r"a": none) }"#,
+            "App { a: int(99999999999999999999) }",
+            "App { a: float(1e400) }",
+            r#"App { a: text("\u{D800}") }"#,
+            "App { a: text(\"long\") }",
+            "App { a: list(list(list())) }",
+            "A {} B {} C {} D {} E {}",
+            "App { a: int(1.5) }",
+            "App { a: float(1) }",
+            "App { a: list(int(1) int(2)) }",
+            "App { a: list(,) }",
+            "App { a: }",
+            "App { a int(1) }",
+            "App",
+            "{}",
+        ];
+
+        for input in inputs {
+            compare(input, &strict);
+            compare(input, &lenient);
+            compare(input, &limited);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Splits the input into tokens for the parser.
+
+use crate::error::{ParseError, ParseErrorKind};
+use std::ops::Range;
+
+/// The kind of a [Token].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    /// A name, such as the name of a block, a field or a
+    /// value type. `inf` and `nan` are also identifiers.
+    Ident,
+    /// An int or float, such as `-1`, `2.5e-3` or `-inf`.
+    Number,
+    /// Text in quotes, with its escapes checked but not resolved.
+    QuotedString,
+    /// Raw text, such as `r"..."` or `r#"..."#`.
+    RawString,
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    Colon,
+    Comma,
+    /// The end of the input.
+    Eof,
+}
+
+/// A token and its location in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Token {
+    pub(crate) kind: TokenKind,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+impl Token {
+    pub(crate) fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+pub(crate) struct Lexer<'i> {
+    input: &'i str,
+    bytes: &'i [u8],
+    pos: usize,
+}
+
+fn is_ident_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_'
+}
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+impl<'i> Lexer<'i> {
+    pub(crate) fn new(input: &'i str) -> Self {
+        Self {
+            input,
+            bytes: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    /// Starts lexing at `pos`, which must be
+    /// between two tokens.
+    pub(crate) fn starting_at(input: &'i str, pos: usize) -> Self {
+        Self {
+            pos,
+            ..Self::new(input)
+        }
+    }
+
+    fn peek_byte(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn error(&self, message: impl Into<String>, range: Range<usize>) -> ParseError {
+        ParseError::new(ParseErrorKind::Syntax, message, self.input, range)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) {
+        while let Some(b) = self.peek_byte(0) {
+            match b {
+                b' ' | b'\t' | b'\n' | b'\r' => self.pos += 1,
+                b'#' => {
+                    self.pos = self.input[self.pos..]
+                        .find('\n')
+                        .map_or(self.bytes.len(), |n| self.pos + n + 1);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Returns the next token, or a token of kind [TokenKind::Eof]
+    /// at the end of the input.
+    pub(crate) fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_trivia();
+
+        let start = self.pos;
+        let Some(b) = self.peek_byte(0) else {
+            return Ok(Token {
+                kind: TokenKind::Eof,
+                start,
+                end: start,
+            });
+        };
+
+        let kind = match b {
+            b'(' => self.single(TokenKind::OpenParen),
+            b')' => self.single(TokenKind::CloseParen),
+            b'{' => self.single(TokenKind::OpenBrace),
+            b'}' => self.single(TokenKind::CloseBrace),
+            b':' => self.single(TokenKind::Colon),
+            b',' => self.single(TokenKind::Comma),
+            b'"' => self.quoted_string()?,
+            b'r' if self.is_raw_string() => self.raw_string()?,
+            b'-' | b'0'..=b'9' => self.number()?,
+            b if is_ident_start(b) => {
+                self.ident();
+                TokenKind::Ident
+            }
+            _ => {
+                let c = self.input[start..].chars().next().unwrap_or_default();
+                return Err(self.error(
+                    format!("unexpected character '{}'", c.escape_debug()),
+                    start..start + c.len_utf8(),
+                ));
+            }
+        };
+
+        Ok(Token {
+            kind,
+            start,
+            end: self.pos,
+        })
+    }
+
+    fn single(&mut self, kind: TokenKind) -> TokenKind {
+        self.pos += 1;
+        kind
+    }
+
+    fn ident(&mut self) {
+        while self.peek_byte(0).is_some_and(is_ident) {
+            self.pos += 1;
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.peek_byte(0).is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    /// Lexes `-?digits`, with an optional fraction and exponent,
+    /// or `-inf` and `-nan`.
+    fn number(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.pos;
+
+        if self.peek_byte(0) == Some(b'-') {
+            self.pos += 1;
+
+            if self.peek_byte(0).is_some_and(is_ident_start) {
+                self.ident();
+                return match &self.input[start + 1..self.pos] {
+                    "inf" | "nan" => Ok(TokenKind::Number),
+                    _ => Err(self.error("invalid number", start..self.pos)),
+                };
+            }
+        }
+
+        if self.digits() == 0 {
+            return Err(self.error("invalid number", start..self.pos));
+        }
+
+        if self.peek_byte(0) == Some(b'.') {
+            self.pos += 1;
+            self.digits();
+        }
+
+        // an exponent is only part of the number if it has digits
+        if matches!(self.peek_byte(0), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(self.peek_byte(1), Some(b'+' | b'-')));
+            if self.peek_byte(1 + sign).is_some_and(|b| b.is_ascii_digit()) {
+                self.pos += 1 + sign;
+                self.digits();
+            }
+        }
+
+        Ok(TokenKind::Number)
+    }
+
+    fn is_raw_string(&self) -> bool {
+        let hashes = self.bytes[self.pos + 1..]
+            .iter()
+            .take_while(|b| **b == b'#')
+            .count();
+        self.peek_byte(1 + hashes) == Some(b'"')
+    }
+
+    fn raw_string(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.pos;
+        let hashes = self.bytes[start + 1..]
+            .iter()
+            .take_while(|b| **b == b'#')
+            .count();
+        let close = format!("\"{}", "#".repeat(hashes));
+        let body = start + hashes + 2;
+
+        match self.input[body..].find(&close) {
+            Some(n) => {
+                self.pos = body + n + close.len();
+                Ok(TokenKind::RawString)
+            }
+            None => Err(self.error("unterminated raw text", start..self.bytes.len())),
+        }
+    }
+
+    /// Lexes text in quotes, checking that every escape is one
+    /// of `\" \\ \n \t \r \0` or `\u{...}` with 1 to 6 hex digits.
+    fn quoted_string(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+
+        loop {
+            match self.peek_byte(0) {
+                None => return Err(self.error("unterminated text", start..self.bytes.len())),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(TokenKind::QuotedString);
+                }
+                Some(b'\\') => {
+                    let escape = self.pos;
+                    match self.peek_byte(1) {
+                        Some(b'"' | b'\\' | b'n' | b't' | b'r' | b'0') => self.pos += 2,
+                        Some(b'u') if self.peek_byte(2) == Some(b'{') => {
+                            self.pos += 3;
+                            let hex = self.bytes[self.pos..]
+                                .iter()
+                                .take_while(|b| b.is_ascii_hexdigit())
+                                .count();
+                            self.pos += hex;
+
+                            if !(1..=6).contains(&hex) || self.peek_byte(0) != Some(b'}') {
+                                return Err(self.error("invalid escape sequence", escape..self.pos));
+                            }
+                            self.pos += 1;
+                        }
+                        _ => {
+                            let len = self.input[escape + 1..]
+                                .chars()
+                                .next()
+                                .map_or(0, char::len_utf8);
+                            return Err(
+                                self.error("invalid escape sequence", escape..escape + 1 + len)
+                            );
+                        }
+                    }
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+}
@@ -1,30 +1,304 @@
 #![doc = include_str!("../README.md")]
 
 mod de;
+mod document;
 pub mod error;
+#[cfg(feature = "pest")]
+pub mod grammar;
+mod lexer;
+mod literal;
+pub mod options;
 mod parser;
+mod reader;
 mod se;
+pub mod span;
+mod value;
 
-use pest_derive::Parser;
-use std::collections::HashMap;
+pub use de::{
+    from_reader_iter, from_reader_iter_with, from_str, from_str_document, from_str_document_with,
+    from_str_named, from_str_named_iter, from_str_named_iter_with, from_str_named_with,
+    from_str_with,
+};
+pub use document::Document;
+pub use error::Error;
+pub use indexmap::IndexMap;
+pub use parser::{parse_input, parse_input_spanned, parse_input_spanned_with, parse_input_with};
+pub use reader::BlockReader;
+pub use se::{
+    to_fmt_writer, to_fmt_writer_pretty, to_pretty_string, to_pretty_string_document, to_string,
+    to_string_document, to_writer, to_writer_pretty,
+};
+pub use value::{from_value, to_value};
 
-pub use de::{from_str, from_str_named, from_str_named_iter};
-pub use parser::parse_input;
-pub use se::{to_pretty_string, to_string};
-
-#[derive(Parser)]
-#[grammar = "../grammar/grammar.pest"]
-pub(crate) struct SirtParser;
+use error::{PathSegment, ValueError};
+use std::borrow::Cow;
 
 /// An enum representing supported data types
 /// in Sirt.
+///
+/// Text, names and keys borrow from the input they were
+/// parsed from where possible. Use [Value::into_owned]
+/// to keep a value after the input is dropped.
 #[derive(Debug, PartialEq, Clone)]
-pub enum Value {
-    Text(String),
+pub enum Value<'a> {
+    Text(Cow<'a, str>),
     Int(types::Int),
     Float(types::Float),
     Bool(bool),
-    List(Vec<Value>),
+    List(Vec<Value<'a>>),
+    Map(IndexMap<Cow<'a, str>, Value<'a>>),
+    Block(Block<'a>),
+    None,
+}
+
+impl<'a> Value<'a> {
+    /// Returns the name of the type of the value,
+    /// as written in Sirt, such as `int` or `text`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Text(_) => "text",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Block(_) => "block",
+            Value::None => "none",
+        }
+    }
+
+    /// Returns the int, if the value is one.
+    pub fn as_int(&self) -> Option<types::Int> {
+        match self {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Returns the float, if the value is one.
+    pub fn as_float(&self) -> Option<types::Float> {
+        match self {
+            Value::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Returns the bool, if the value is one.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns the text, if the value is text.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements, if the value is a list.
+    pub fn as_list(&self) -> Option<&[Value<'a>]> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Same as [Value::as_list], but allows editing the list.
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value<'a>>> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries, if the value is a map.
+    pub fn as_map(&self) -> Option<&IndexMap<Cow<'a, str>, Value<'a>>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Same as [Value::as_map], but allows editing the map.
+    pub fn as_map_mut(&mut self) -> Option<&mut IndexMap<Cow<'a, str>, Value<'a>>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Returns the block, if the value is a nested block.
+    pub fn as_block(&self) -> Option<&Block<'a>> {
+        match self {
+            Value::Block(block) => Some(block),
+            _ => None,
+        }
+    }
+
+    /// Same as [Value::as_block], but allows editing the block.
+    pub fn as_block_mut(&mut self) -> Option<&mut Block<'a>> {
+        match self {
+            Value::Block(block) => Some(block),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is `none`.
+    pub fn is_none(&self) -> bool {
+        matches!(self, Value::None)
+    }
+
+    /// Copies any borrowed text, so the value no
+    /// longer borrows from the input.
+    pub fn into_owned(self) -> Value<'static> {
+        match self {
+            Value::Text(text) => Value::Text(Cow::Owned(text.into_owned())),
+            Value::Int(n) => Value::Int(n),
+            Value::Float(n) => Value::Float(n),
+            Value::Bool(b) => Value::Bool(b),
+            Value::List(list) => Value::List(list.into_iter().map(Value::into_owned).collect()),
+            Value::Map(map) => Value::Map(
+                map.into_iter()
+                    .map(|(key, value)| (Cow::Owned(key.into_owned()), value.into_owned()))
+                    .collect(),
+            ),
+            Value::Block(block) => Value::Block(block.into_owned()),
+            Value::None => Value::None,
+        }
+    }
+}
+
+impl From<types::Int> for Value<'_> {
+    fn from(n: types::Int) -> Self {
+        Value::Int(n)
+    }
+}
+
+impl From<types::Float> for Value<'_> {
+    fn from(n: types::Float) -> Self {
+        Value::Float(n)
+    }
+}
+
+impl From<bool> for Value<'_> {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(text: &'a str) -> Self {
+        Value::Text(Cow::Borrowed(text))
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(text: String) -> Self {
+        Value::Text(Cow::Owned(text))
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Value<'a> {
+    fn from(text: Cow<'a, str>) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl<'a> From<Vec<Value<'a>>> for Value<'a> {
+    fn from(list: Vec<Value<'a>>) -> Self {
+        Value::List(list)
+    }
+}
+
+impl<'a> From<IndexMap<Cow<'a, str>, Value<'a>>> for Value<'a> {
+    fn from(map: IndexMap<Cow<'a, str>, Value<'a>>) -> Self {
+        Value::Map(map)
+    }
+}
+
+impl<'a> From<Block<'a>> for Value<'a> {
+    fn from(block: Block<'a>) -> Self {
+        Value::Block(block)
+    }
+}
+
+/// Converts a value into one of the [types], failing
+/// with a [ValueError] if it has a different type.
+macro_rules! impl_try_from_value {
+    ($($ty:ty => $variant:ident($name:literal)),*) => {
+        $(
+            impl TryFrom<Value<'_>> for $ty {
+                type Error = ValueError;
+
+                fn try_from(value: Value<'_>) -> Result<Self, Self::Error> {
+                    match value {
+                        Value::$variant(v) => Ok(v.into()),
+                        other => Err(ValueError::type_mismatch($name, other.type_name())),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value!(
+    types::Int => Int("int"),
+    types::Float => Float("float"),
+    bool => Bool("bool"),
+    types::Text => Text("text")
+);
+
+impl<'a, T> TryFrom<Value<'a>> for Vec<T>
+where
+    T: TryFrom<Value<'a>, Error = ValueError>,
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::List(list) => list
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| T::try_from(v).map_err(|err| err.prepend(PathSegment::Index(i))))
+                .collect(),
+            other => Err(ValueError::type_mismatch("list", other.type_name())),
+        }
+    }
+}
+
+impl<'a, T> TryFrom<Value<'a>> for IndexMap<String, T>
+where
+    T: TryFrom<Value<'a>, Error = ValueError>,
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Map(map) => map
+                .into_iter()
+                .map(|(key, v)| match T::try_from(v) {
+                    Ok(v) => Ok((key.into_owned(), v)),
+                    Err(err) => Err(err.prepend(PathSegment::Key(key.into_owned()))),
+                })
+                .collect(),
+            other => Err(ValueError::type_mismatch("map", other.type_name())),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Block<'a> {
+    type Error = ValueError;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Block(block) => Ok(block),
+            other => Err(ValueError::type_mismatch("block", other.type_name())),
+        }
+    }
 }
 
 /// Module containing the primitive types of Sirt.
@@ -37,6 +311,7 @@ pub mod types {
     pub type Bool = bool;
     pub type Text = String;
     pub type List<T> = Vec<T>;
+    pub type Map<T> = indexmap::IndexMap<String, T>;
     pub type ListText = Vec<String>;
     pub type ListInt = Vec<Int>;
     pub type ListFloat = Vec<Float>;
@@ -49,20 +324,193 @@ pub mod types {
 /// fields (or none), starting with an
 /// identifier, followed by a type with a
 /// value.
+///
+/// The name of a block and of its fields should be
+/// identifiers, such as `Package` or `dev_dependency`,
+/// for the block to be written as Sirt.
+///
+/// ```
+/// use libsirt::{Block, Value};
+///
+/// let mut block = Block::new("Server")
+///     .with_field("host", "localhost")
+///     .with_field("port", 8080);
+///
+/// block.insert_field("port", 8081);
+/// assert_eq!(block.get("port"), Some(&Value::Int(8081)));
+/// ```
 #[derive(Debug, PartialEq, Clone)]
-pub struct Block {
-    name: String,
-    fields: HashMap<String, Value>,
+pub struct Block<'a> {
+    name: Cow<'a, str>,
+    fields: IndexMap<Cow<'a, str>, Value<'a>>,
 }
 
-impl Block {
+impl<'a> Block<'a> {
+    /// Creates a block without any fields.
+    pub fn new(name: impl Into<Cow<'a, str>>) -> Self {
+        Self::with_fields(name, IndexMap::new())
+    }
+
+    /// Creates a block with the given fields.
+    pub fn with_fields(
+        name: impl Into<Cow<'a, str>>,
+        fields: IndexMap<Cow<'a, str>, Value<'a>>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            fields,
+        }
+    }
+
+    /// Adds a field to the block, replacing any field with
+    /// the same name, so that blocks can be built in one go.
+    pub fn with_field(mut self, key: impl Into<Cow<'a, str>>, value: impl Into<Value<'a>>) -> Self {
+        self.insert_field(key, value);
+        self
+    }
+
     /// Returns the name of the Block
     pub fn get_name(&self) -> &str {
         &self.name
     }
 
-    /// Returns a reference to the `fields` field.
-    pub fn get_fields(&self) -> &HashMap<String, Value> {
+    /// Changes the name of the Block.
+    pub fn set_name(&mut self, name: impl Into<Cow<'a, str>>) {
+        self.name = name.into();
+    }
+
+    /// Returns a reference to the `fields` field,
+    /// which keeps the fields in the order they were written.
+    pub fn get_fields(&self) -> &IndexMap<Cow<'a, str>, Value<'a>> {
         &self.fields
     }
+
+    /// Returns the value of a field.
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        self.fields.get(key)
+    }
+
+    /// Returns the value of a field so that it can be edited.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value<'a>> {
+        self.fields.get_mut(key)
+    }
+
+    /// Returns the value of a field, converted by `f`, or an
+    /// error naming the block and field if it is missing or
+    /// `f` returns `None`.
+    fn get_typed<'s, T>(
+        &'s self,
+        key: &str,
+        expected: &'static str,
+        f: impl FnOnce(&'s Value<'a>) -> Option<T>,
+    ) -> Result<T, ValueError> {
+        let block = || PathSegment::Block(self.name.to_string());
+
+        let value = self
+            .fields
+            .get(key)
+            .ok_or_else(|| ValueError::missing_field(key).prepend(block()))?;
+
+        f(value).ok_or_else(|| {
+            ValueError::type_mismatch(expected, value.type_name())
+                .prepend(PathSegment::Field(key.to_string()))
+                .prepend(block())
+        })
+    }
+
+    /// Returns the int in a field.
+    ///
+    /// # Error
+    /// If there is no such field, or if it is not an int, it
+    /// returns an Err([ValueError]), such as
+    /// `Server.port: expected int, found text`.
+    pub fn get_int(&self, key: &str) -> Result<types::Int, ValueError> {
+        self.get_typed(key, "int", Value::as_int)
+    }
+
+    /// Same as [Block::get_int], but for a float.
+    pub fn get_float(&self, key: &str) -> Result<types::Float, ValueError> {
+        self.get_typed(key, "float", Value::as_float)
+    }
+
+    /// Same as [Block::get_int], but for a bool.
+    pub fn get_bool(&self, key: &str) -> Result<bool, ValueError> {
+        self.get_typed(key, "bool", Value::as_bool)
+    }
+
+    /// Same as [Block::get_int], but for text.
+    pub fn get_text(&self, key: &str) -> Result<&str, ValueError> {
+        self.get_typed(key, "text", Value::as_text)
+    }
+
+    /// Same as [Block::get_int], but for a list.
+    pub fn get_list(&self, key: &str) -> Result<&[Value<'a>], ValueError> {
+        self.get_typed(key, "list", Value::as_list)
+    }
+
+    /// Same as [Block::get_int], but for a map.
+    pub fn get_map(&self, key: &str) -> Result<&IndexMap<Cow<'a, str>, Value<'a>>, ValueError> {
+        self.get_typed(key, "map", Value::as_map)
+    }
+
+    /// Same as [Block::get_int], but for a nested block.
+    pub fn get_block(&self, key: &str) -> Result<&Block<'a>, ValueError> {
+        self.get_typed(key, "block", Value::as_block)
+    }
+
+    /// Sets the value of a field, returning its old value.
+    ///
+    /// A new field is added after the others, while a field
+    /// that already exists keeps its place.
+    pub fn insert_field(
+        &mut self,
+        key: impl Into<Cow<'a, str>>,
+        value: impl Into<Value<'a>>,
+    ) -> Option<Value<'a>> {
+        self.fields.insert(key.into(), value.into())
+    }
+
+    /// Removes a field, returning its value, and
+    /// keeps the order of the other fields.
+    pub fn remove_field(&mut self, key: &str) -> Option<Value<'a>> {
+        self.fields.shift_remove(key)
+    }
+
+    /// Renames the field `from` to `to`, keeping its place.
+    ///
+    /// Returns false, without changing the block, if there
+    /// is no field `from` or if there already is a field `to`.
+    /// Renaming a field to its own name changes nothing, and
+    /// returns whether the field exists.
+    pub fn rename_field(&mut self, from: &str, to: impl Into<Cow<'a, str>>) -> bool {
+        let to = to.into();
+        if from == to {
+            return self.fields.contains_key(from);
+        }
+
+        if self.fields.contains_key(&to) {
+            return false;
+        }
+
+        let Some(index) = self.fields.get_index_of(from) else {
+            return false;
+        };
+
+        let (_, value) = self.fields.shift_remove_index(index).unwrap();
+        self.fields.shift_insert(index, to, value);
+        true
+    }
+
+    /// Copies any borrowed text, so the block no
+    /// longer borrows from the input.
+    pub fn into_owned(self) -> Block<'static> {
+        Block {
+            name: Cow::Owned(self.name.into_owned()),
+            fields: self
+                .fields
+                .into_iter()
+                .map(|(key, value)| (Cow::Owned(key.into_owned()), value.into_owned()))
+                .collect(),
+        }
+    }
 }
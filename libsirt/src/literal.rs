@@ -0,0 +1,196 @@
+//! Conversion of literals into values, shared by the parser
+//! and the pest grammar.
+//!
+//! Every function takes the whole input and the range of the
+//! literal in it, so errors can be located.
+
+use crate::Value;
+use crate::error::{ParseError, ParseErrorKind};
+use crate::options::{DuplicateKeys, ParseOptions};
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::ops::Range;
+
+pub(crate) fn parse_int<'i>(input: &'i str, range: Range<usize>) -> Result<Value<'i>, ParseError> {
+    let s = &input[range.clone()];
+    let n: i64 = s.parse().map_err(|err: std::num::ParseIntError| {
+        ParseError::new(
+            ParseErrorKind::Int,
+            format!("invalid int '{s}', {err}"),
+            input,
+            range,
+        )
+    })?;
+    Ok(Value::Int(n))
+}
+
+pub(crate) fn parse_float<'i>(
+    input: &'i str,
+    range: Range<usize>,
+) -> Result<Value<'i>, ParseError> {
+    let s = &input[range.clone()];
+    let error =
+        |message: String| ParseError::new(ParseErrorKind::Float, message, input, range.clone());
+
+    let n: f64 = s
+        .parse()
+        .map_err(|err: std::num::ParseFloatError| error(format!("invalid float '{s}', {err}")))?;
+
+    if n.is_infinite() && !s.ends_with("inf") {
+        return Err(error(format!(
+            "invalid float '{s}', number too large to fit in target type"
+        )));
+    }
+
+    Ok(Value::Float(n))
+}
+
+pub(crate) fn parse_bool<'i>(
+    input: &'i str,
+    range: Range<usize>,
+    options: &ParseOptions,
+) -> Result<Value<'i>, ParseError> {
+    let lenient = options.get_lenient_bools();
+
+    match &input[range.clone()] {
+        "true" | "yes" => Ok(Value::Bool(true)),
+        "false" | "no" => Ok(Value::Bool(false)),
+        "on" | "1" if lenient => Ok(Value::Bool(true)),
+        "off" | "0" if lenient => Ok(Value::Bool(false)),
+        other => {
+            let mut expected = vec!["true", "false", "yes", "no"];
+            if lenient {
+                expected.extend(["on", "off", "1", "0"]);
+            }
+
+            Err(ParseError::new(
+                ParseErrorKind::Bool,
+                format!("invalid bool '{other}'"),
+                input,
+                range,
+            )
+            .with_expected(expected.into_iter().map(String::from).collect()))
+        }
+    }
+}
+
+/// Returns the text of a quoted or raw string, which must
+/// already be known to be well-formed.
+///
+/// The text is borrowed from the input unless it has escapes.
+pub(crate) fn parse_text<'i>(
+    input: &'i str,
+    range: Range<usize>,
+    options: &ParseOptions,
+) -> Result<Cow<'i, str>, ParseError> {
+    let s = &input[range.clone()];
+
+    let text = if let Some(raw) = s.strip_prefix('r') {
+        let hashes = raw.chars().take_while(|c| *c == '#').count();
+        Cow::Borrowed(&raw[hashes + 1..raw.len() - hashes - 1])
+    } else if !s.contains('\\') {
+        Cow::Borrowed(&s[1..s.len() - 1])
+    } else {
+        Cow::Owned(unescape(input, range.clone())?)
+    };
+
+    options.check_text(&text, input, range)?;
+    Ok(text)
+}
+
+fn unescape(input: &str, range: Range<usize>) -> Result<String, ParseError> {
+    let s = &input[range.start + 1..range.end - 1];
+    let mut output = String::with_capacity(s.len());
+    let mut chars = s.char_indices();
+
+    let error_at = |start: usize, end: usize, message: String| {
+        // the offsets are relative to the text after the opening quote
+        let offset = range.start + 1;
+        ParseError::new(
+            ParseErrorKind::Text,
+            message,
+            input,
+            offset + start..offset + end,
+        )
+    };
+
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some((_, '"')) => output.push('"'),
+            Some((_, '\\')) => output.push('\\'),
+            Some((_, 'n')) => output.push('\n'),
+            Some((_, 't')) => output.push('\t'),
+            Some((_, 'r')) => output.push('\r'),
+            Some((_, '0')) => output.push('\0'),
+            Some((_, 'u')) => {
+                let hex: String = chars
+                    .by_ref()
+                    .skip(1)
+                    .map(|(_, c)| c)
+                    .take_while(|c| *c != '}')
+                    .collect();
+                let c = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        error_at(
+                            start,
+                            start + hex.len() + 4,
+                            format!("invalid escape '\\u{{{hex}}}', not a unicode code point"),
+                        )
+                    })?;
+                output.push(c);
+            }
+            other => {
+                let end = other.map_or(start + 1, |(i, c)| i + c.len_utf8());
+                return Err(error_at(
+                    start,
+                    end,
+                    format!("unknown escape sequence '{}'", &s[start..end]),
+                ));
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Inserts a field or map entry, following the duplicate
+/// key policy of the options.
+///
+/// `range` is the location of the key, and `what` is
+/// used in the error message.
+pub(crate) fn insert_key<'i, V>(
+    map: &mut IndexMap<Cow<'i, str>, V>,
+    key: Cow<'i, str>,
+    value: V,
+    options: &ParseOptions,
+    input: &str,
+    range: Range<usize>,
+    what: &str,
+) -> Result<(), ParseError> {
+    match options.get_duplicate_keys() {
+        _ if !map.contains_key(&key) => {
+            map.insert(key, value);
+        }
+        DuplicateKeys::Reject => {
+            return Err(ParseError::new(
+                ParseErrorKind::DuplicateKey,
+                format!("duplicate {what} '{key}'"),
+                input,
+                range,
+            ));
+        }
+        DuplicateKeys::FirstWins => {}
+        DuplicateKeys::LastWins => {
+            map.insert(key, value);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,242 @@
+//! Options that change how the input is parsed.
+//!
+//! The default options are strict, and are the ones
+//! used by [crate::parse_input].
+//!
+//! When parsing untrusted input, set all of the limits,
+//! as only the nesting depth is limited by default.
+
+use crate::error::{ParseError, ParseErrorKind};
+use std::ops::Range;
+
+/// The default for [ParseOptions::max_depth].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// What the parser does when a key appears more than
+/// once in the same block or map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Return a [crate::error::ParseError] located at the
+    /// second occurrence of the key.
+    #[default]
+    Reject,
+    /// Keep the value of the first occurrence.
+    FirstWins,
+    /// Keep the value of the last occurrence.
+    LastWins,
+}
+
+/// Options for [crate::parse_input_with] and [crate::from_str_with].
+///
+/// ```
+/// use libsirt::options::{DuplicateKeys, ParseOptions};
+///
+/// let options = ParseOptions::new()
+///     .duplicate_keys(DuplicateKeys::LastWins)
+///     .lenient_bools(true)
+///     .trailing_commas(true);
+///
+/// let input = "A { x: int(1) x: int(2) on: bool(on) list: list(int(1),) }";
+/// let blocks = libsirt::parse_input_with(input, &options).unwrap();
+///
+/// assert_eq!(blocks[0].get_fields()["x"], libsirt::Value::Int(2));
+/// assert_eq!(blocks[0].get_fields()["on"], libsirt::Value::Bool(true));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    duplicate_keys: DuplicateKeys,
+    lenient_bools: bool,
+    trailing_commas: bool,
+    max_depth: Option<usize>,
+    max_input_size: Option<usize>,
+    max_values: Option<usize>,
+    max_text_length: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            duplicate_keys: DuplicateKeys::default(),
+            lenient_bools: false,
+            trailing_commas: false,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_input_size: None,
+            max_values: None,
+            max_text_length: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Returns the default, strict options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what happens when a key appears more than once.
+    /// Defaults to [DuplicateKeys::Reject].
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Also accepts `on`, `off`, `1` and `0` as bools, on top
+    /// of `true`, `false`, `yes` and `no`. Defaults to `false`.
+    pub fn lenient_bools(mut self, enabled: bool) -> Self {
+        self.lenient_bools = enabled;
+        self
+    }
+
+    /// Accepts a comma after the last value of a list or
+    /// entry of a map. Defaults to `false`.
+    pub fn trailing_commas(mut self, enabled: bool) -> Self {
+        self.trailing_commas = enabled;
+        self
+    }
+
+    /// Sets how deeply blocks, lists and maps can be nested,
+    /// where a top-level block has a depth of 1. Defaults to
+    /// [DEFAULT_MAX_DEPTH].
+    ///
+    /// Without a limit, deeply nested input can overflow
+    /// the stack.
+    pub fn max_depth(mut self, depth: impl Into<Option<usize>>) -> Self {
+        self.max_depth = depth.into();
+        self
+    }
+
+    /// Sets the largest input, in bytes, that will be parsed.
+    /// Defaults to no limit.
+    pub fn max_input_size(mut self, size: impl Into<Option<usize>>) -> Self {
+        self.max_input_size = size.into();
+        self
+    }
+
+    /// Sets how many values the input can have in total, counting
+    /// blocks and every nested value. Defaults to no limit.
+    pub fn max_values(mut self, count: impl Into<Option<usize>>) -> Self {
+        self.max_values = count.into();
+        self
+    }
+
+    /// Sets the longest text, in bytes, that a text value or map
+    /// key can have after escapes are resolved. Defaults to no limit.
+    pub fn max_text_length(mut self, length: impl Into<Option<usize>>) -> Self {
+        self.max_text_length = length.into();
+        self
+    }
+
+    /// Returns the policy for duplicate keys.
+    pub fn get_duplicate_keys(&self) -> DuplicateKeys {
+        self.duplicate_keys
+    }
+
+    /// Returns whether `on`, `off`, `1` and `0` are accepted as bools.
+    pub fn get_lenient_bools(&self) -> bool {
+        self.lenient_bools
+    }
+
+    /// Returns whether trailing commas are accepted.
+    pub fn get_trailing_commas(&self) -> bool {
+        self.trailing_commas
+    }
+
+    /// Returns the maximum nesting depth, if there is one.
+    pub fn get_max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Returns the maximum input size in bytes, if there is one.
+    pub fn get_max_input_size(&self) -> Option<usize> {
+        self.max_input_size
+    }
+
+    /// Returns the maximum number of values, if there is one.
+    pub fn get_max_values(&self) -> Option<usize> {
+        self.max_values
+    }
+
+    /// Returns the maximum length of text in bytes, if there is one.
+    pub fn get_max_text_length(&self) -> Option<usize> {
+        self.max_text_length
+    }
+}
+
+// checks of the limits, shared by the parsers
+impl ParseOptions {
+    pub(crate) fn check_input_size(&self, input: &str) -> Result<(), ParseError> {
+        match self.max_input_size {
+            Some(max) if input.len() > max => {
+                let start = (0..=max).rev().find(|i| input.is_char_boundary(*i));
+                let start = start.unwrap_or(0);
+
+                Err(ParseError::new(
+                    ParseErrorKind::InputSizeLimit,
+                    format!(
+                        "input is {} bytes, which is larger than the limit of {max}",
+                        input.len()
+                    ),
+                    input,
+                    start..start,
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// `range` is the location of the block, list or map
+    /// that opens the given depth.
+    pub(crate) fn check_depth(
+        &self,
+        depth: usize,
+        input: &str,
+        range: Range<usize>,
+    ) -> Result<(), ParseError> {
+        match self.max_depth {
+            Some(max) if depth > max => Err(ParseError::new(
+                ParseErrorKind::DepthLimit,
+                format!("nesting is deeper than the limit of {max}"),
+                input,
+                range,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_values(
+        &self,
+        count: usize,
+        input: &str,
+        range: Range<usize>,
+    ) -> Result<(), ParseError> {
+        match self.max_values {
+            Some(max) if count > max => Err(ParseError::new(
+                ParseErrorKind::ValueCountLimit,
+                format!("input has more than {max} values"),
+                input,
+                range,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_text(
+        &self,
+        text: &str,
+        input: &str,
+        range: Range<usize>,
+    ) -> Result<(), ParseError> {
+        match self.max_text_length {
+            Some(max) if text.len() > max => Err(ParseError::new(
+                ParseErrorKind::TextLengthLimit,
+                format!(
+                    "text is {} bytes, which is longer than the limit of {max}",
+                    text.len()
+                ),
+                input,
+                range,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,10 +1,14 @@
-use crate::error::ParseError;
+use crate::error::{ParseError, ParseErrorKind};
+use crate::lexer::{Lexer, Token, TokenKind};
+use crate::literal::{insert_key, parse_bool, parse_float, parse_int, parse_text};
+use crate::options::{DuplicateKeys, ParseOptions};
+use crate::span::{BlockSpans, FieldSpans, LineIndex, SourceMap};
 use crate::types::List;
-use crate::{Block, Rule, SirtParser, Value};
+use crate::{Block, Value};
 
-use pest::Parser;
-use pest::iterators::Pair;
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::ops::Range;
 
 /// Break the input down into multiple Blocks.
 ///
@@ -15,102 +19,417 @@ use std::collections::HashMap;
 /// and [crate::to_string] and/or [crate::to_pretty_string]
 /// for serialization.
 ///
+/// This uses the default [ParseOptions], so a key that
+/// appears twice in the same block or map is an error.
+///
 /// # Error
 /// If an error is encountered by the parser,
 /// it will return an Err([crate::error::ParseError]).
-pub fn parse_input(input: &str) -> Result<Vec<Block>, ParseError<'_>> {
-    let mut blocks = Vec::new();
+pub fn parse_input(input: &str) -> Result<Vec<Block<'_>>, ParseError> {
+    parse_input_with(input, &ParseOptions::default())
+}
+
+/// Same as [parse_input], but with the given [ParseOptions].
+pub fn parse_input_with<'i>(
+    input: &'i str,
+    options: &ParseOptions,
+) -> Result<Vec<Block<'i>>, ParseError> {
+    let (blocks, _) = Parser::new(input, options, false)?.parse_document()?;
+    Ok(blocks)
+}
 
-    let mut pairs =
-        SirtParser::parse(Rule::input, input).map_err(|e| ParseError::Input(e.to_string()))?;
+/// Same as [parse_input], but also returns the location of
+/// every block, field and value as a [SourceMap].
+///
+/// The locations of the block at index `n` are found at
+/// index `n` of the source map.
+pub fn parse_input_spanned(input: &str) -> Result<(Vec<Block<'_>>, SourceMap), ParseError> {
+    parse_input_spanned_with(input, &ParseOptions::default())
+}
 
-    if let Some(pairs) = pairs.next() {
-        for pair in pairs.into_inner() {
-            if pair.as_rule() == Rule::block {
-                blocks.push(parse_block(pair)?);
+/// Same as [parse_input_spanned], but with the given [ParseOptions].
+pub fn parse_input_spanned_with<'i>(
+    input: &'i str,
+    options: &ParseOptions,
+) -> Result<(Vec<Block<'i>>, SourceMap), ParseError> {
+    Parser::new(input, options, true)?.parse_document()
+}
+
+/// What may appear in place of a value, in the same
+/// words as the errors of the pest grammar.
+const VALUES: [&str; 8] = [
+    "text(...)",
+    "int(...)",
+    "float(...)",
+    "bool(...)",
+    "list(...)",
+    "map(...)",
+    "none",
+    "block",
+];
+
+/// A recursive descent parser over the tokens of the [Lexer].
+struct Parser<'i, 'o> {
+    input: &'i str,
+    lexer: Lexer<'i>,
+    peeked: Option<Token>,
+    /// The end of the last token that was consumed.
+    last_end: usize,
+    options: &'o ParseOptions,
+    /// Only built when the locations are asked for.
+    lines: Option<LineIndex<'i>>,
+    depth: usize,
+    values: usize,
+}
+
+impl<'i, 'o> Parser<'i, 'o> {
+    fn new(input: &'i str, options: &'o ParseOptions, spanned: bool) -> Result<Self, ParseError> {
+        options.check_input_size(input)?;
+
+        Ok(Self {
+            input,
+            lexer: Lexer::new(input),
+            peeked: None,
+            last_end: 0,
+            options,
+            lines: spanned.then(|| LineIndex::new(input)),
+            depth: 0,
+            values: 0,
+        })
+    }
+
+    fn peek(&mut self) -> Result<Token, ParseError> {
+        match self.peeked {
+            Some(token) => Ok(token),
+            None => {
+                let token = self.lexer.next_token()?;
+                self.peeked = Some(token);
+                Ok(token)
             }
         }
     }
 
-    Ok(blocks)
-}
+    fn next(&mut self) -> Result<Token, ParseError> {
+        let token = self.peek()?;
+        self.peeked = None;
+        self.last_end = token.end;
+        Ok(token)
+    }
 
-fn parse_block(pair: Pair<'_, Rule>) -> Result<Block, ParseError<'_>> {
-    let mut inner = pair.into_inner();
-    let mut fields = HashMap::new();
+    fn text(&self, token: Token) -> &'i str {
+        &self.input[token.range()]
+    }
 
-    let name = inner.next().ok_or(ParseError::Block)?.as_str().to_string();
+    fn unexpected(&self, token: Token, expected: &[&str]) -> ParseError {
+        ParseError::new(
+            ParseErrorKind::Syntax,
+            "unexpected input",
+            self.input,
+            token.start..token.start,
+        )
+        .with_expected(expected.iter().map(|s| s.to_string()).collect())
+    }
 
-    for field in inner {
-        let mut parts = field.into_inner();
-        let key = parts.next().ok_or(ParseError::Field)?.as_str().to_string();
-        let value = parse_value(parts.next().ok_or(ParseError::Value)?)?;
-        fields.insert(key, value);
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Token, ParseError> {
+        let token = self.next()?;
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(self.unexpected(token, &[expected]))
+        }
     }
 
-    Ok(Block { name, fields })
-}
+    /// Enters a block, list or map, where `range` covers
+    /// its name up to the opening bracket.
+    fn enter(&mut self, range: Range<usize>) -> Result<(), ParseError> {
+        self.depth += 1;
+        self.options.check_depth(self.depth, self.input, range)
+    }
 
-fn parse_value(pair: Pair<'_, Rule>) -> Result<Value, ParseError<'_>> {
-    match pair.as_rule() {
-        Rule::text => {
-            let s = pair.into_inner().next().ok_or(ParseError::Value)?.as_str();
-            let txt = s[1..s.len() - 1].to_string();
-            Ok(Value::Text(txt))
+    /// Counts a block or value that has been parsed.
+    fn count(&mut self, range: Range<usize>) -> Result<(), ParseError> {
+        self.values += 1;
+        self.options.check_values(self.values, self.input, range)
+    }
+
+    fn parse_document(mut self) -> Result<(Vec<Block<'i>>, SourceMap), ParseError> {
+        let mut blocks = Vec::new();
+        let mut source_map = SourceMap::default();
+
+        loop {
+            let token = self.next()?;
+            match token.kind {
+                TokenKind::Eof => break,
+                TokenKind::Ident => {
+                    let (block, spans) = self.parse_block(token)?;
+                    self.count(token.start..self.last_end)?;
+
+                    blocks.push(block);
+                    source_map.blocks.extend(spans);
+                }
+                _ => return Err(self.unexpected(token, &["block", "end of input"])),
+            }
         }
-        Rule::int => Ok(parse_int(pair)?),
-        Rule::float => Ok(parse_float(pair)?),
-        Rule::bool => {
-            let s = pair.as_str();
-            let s: &String = &s[5..].chars().take_while(|c| *c != ')').collect();
-
-            match s.as_str().trim() {
-                "true" | "yes" => Ok(Value::Bool(true)),
-                "false" | "no" => Ok(Value::Bool(false)),
-                other => Err(ParseError::Bool(format!(
-                    "value '{other}':\nexpected boolean value from: [true, false, yes, no]"
-                ))),
+
+        Ok((blocks, source_map))
+    }
+
+    /// Parses a block after its name, returning its locations
+    /// if they were asked for.
+    fn parse_block(&mut self, name: Token) -> Result<(Block<'i>, Option<BlockSpans>), ParseError> {
+        let open = self.expect(TokenKind::OpenBrace, "'{'")?;
+        self.enter(name.start..open.end)?;
+
+        let mut fields = IndexMap::new();
+        let mut spans = IndexMap::new();
+
+        loop {
+            let key = self.next()?;
+            match key.kind {
+                TokenKind::CloseBrace => break,
+                TokenKind::Ident => {}
+                _ => return Err(self.unexpected(key, &["field", "'}'"])),
+            }
+
+            self.expect(TokenKind::Colon, "':'")?;
+            let start = self.peek()?.start;
+            let (value, block) = self.parse_value()?;
+
+            if let Some(lines) = &self.lines {
+                let field = FieldSpans {
+                    key: lines.span(key.range()),
+                    value: lines.span(start..self.last_end),
+                    block: block.map(Box::new),
+                };
+
+                // duplicates are reported when inserting the value
+                if self.options.get_duplicate_keys() == DuplicateKeys::FirstWins {
+                    spans.entry(self.text(key).to_string()).or_insert(field);
+                } else {
+                    spans.insert(self.text(key).to_string(), field);
+                }
             }
+
+            insert_key(
+                &mut fields,
+                Cow::Borrowed(self.text(key)),
+                value,
+                self.options,
+                self.input,
+                key.range(),
+                "field",
+            )?;
         }
-        Rule::list => Ok(Value::List(parse_list(pair)?)),
-        _ => unreachable!(),
+
+        self.depth -= 1;
+
+        let block = Block {
+            name: Cow::Borrowed(self.text(name)),
+            fields,
+        };
+
+        let spans = self.lines.as_ref().map(|lines| BlockSpans {
+            span: lines.span(name.start..self.last_end),
+            name: lines.span(name.range()),
+            fields: spans,
+        });
+
+        Ok((block, spans))
     }
-}
 
-fn parse_list(pair: Pair<'_, Rule>) -> Result<List<Value>, ParseError<'_>> {
-    let mut list = List::with_capacity(1);
+    /// Parses a value, also returning the locations of
+    /// a nested block if they were asked for.
+    fn parse_value(&mut self) -> Result<(Value<'i>, Option<BlockSpans>), ParseError> {
+        let token = self.next()?;
+        if token.kind != TokenKind::Ident {
+            return Err(self.unexpected(token, &VALUES));
+        }
 
-    for inner in pair.into_inner() {
-        if inner.as_rule() == Rule::value_list {
-            for v in inner.into_inner() {
-                list.push(parse_value(v)?);
+        let mut spans = None;
+        let value = match self.text(token) {
+            // a nested block can be named after any keyword
+            _ if self.peek()?.kind == TokenKind::OpenBrace => {
+                let (block, block_spans) = self.parse_block(token)?;
+                spans = block_spans;
+                Value::Block(block)
             }
+            "none" => Value::None,
+            "list" => Value::List(self.parse_list(token)?),
+            "map" => Value::Map(self.parse_map(token)?),
+            "text" | "int" | "float" | "bool" => self.parse_literal(token)?,
+            _ => return Err(self.unexpected(token, &VALUES)),
+        };
+
+        self.count(token.start..self.last_end)?;
+        Ok((value, spans))
+    }
+
+    /// Parses a value written as `keyword(literal)`.
+    ///
+    /// Like the grammar, malformed input inside of the
+    /// parentheses is reported at the start of the value.
+    fn parse_literal(&mut self, keyword: Token) -> Result<Value<'i>, ParseError> {
+        let malformed = |parser: &Self| parser.unexpected(keyword, &VALUES);
+
+        if self.next()?.kind != TokenKind::OpenParen {
+            return Err(malformed(self));
+        }
+        let literal = self.next()?;
+        if self.next()?.kind != TokenKind::CloseParen {
+            return Err(malformed(self));
+        }
+
+        let s = self.text(literal);
+        let is_int = |s: &str| {
+            let digits = s.strip_prefix('-').unwrap_or(s);
+            !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+        };
+
+        match (self.text(keyword), literal.kind) {
+            ("text", TokenKind::QuotedString | TokenKind::RawString) => Ok(Value::Text(
+                parse_text(self.input, literal.range(), self.options)?,
+            )),
+            ("int", TokenKind::Number) if is_int(s) => parse_int(self.input, literal.range()),
+            ("float", TokenKind::Number) if !is_int(s) => parse_float(self.input, literal.range()),
+            ("float", TokenKind::Ident) if s == "inf" || s == "nan" => {
+                parse_float(self.input, literal.range())
+            }
+            ("bool", TokenKind::Ident) => parse_bool(self.input, literal.range(), self.options),
+            ("bool", TokenKind::Number) if is_int(s) => {
+                parse_bool(self.input, literal.range(), self.options)
+            }
+            _ => Err(malformed(self)),
         }
     }
 
-    Ok(list)
-}
+    /// Parses the items of a list or map, up to and including
+    /// the closing parenthesis, calling `item` for each one.
+    fn parse_items(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        if self.peek()?.kind == TokenKind::CloseParen {
+            self.next()?;
+            return Ok(());
+        }
 
-fn parse_int(pair: Pair<'_, Rule>) -> Result<Value, ParseError<'_>> {
-    let s = pair.into_inner().next().ok_or(ParseError::Value)?.as_str();
-    let n: i64 = s
-        .parse()
-        .map_err(|err: std::num::ParseIntError| ParseError::Int(format!("value '{s}':\n{err}")))?;
-    Ok(Value::Int(n))
-}
+        loop {
+            item(self)?;
+
+            let token = self.next()?;
+            match token.kind {
+                TokenKind::CloseParen => return Ok(()),
+                TokenKind::Comma if self.peek()?.kind == TokenKind::CloseParen => {
+                    if !self.options.get_trailing_commas() {
+                        return Err(ParseError::new(
+                            ParseErrorKind::Syntax,
+                            "trailing comma",
+                            self.input,
+                            token.range(),
+                        ));
+                    }
+
+                    self.next()?;
+                    return Ok(());
+                }
+                TokenKind::Comma => {}
+                _ => return Err(self.unexpected(token, &["','", "')'"])),
+            }
+        }
+    }
+
+    fn parse_list(&mut self, keyword: Token) -> Result<List<Value<'i>>, ParseError> {
+        let open = self.expect(TokenKind::OpenParen, "'('")?;
+        self.enter(keyword.start..open.end)?;
+
+        let mut list = List::with_capacity(1);
+        self.parse_items(|parser| {
+            list.push(parser.parse_value()?.0);
+            Ok(())
+        })?;
+
+        self.depth -= 1;
+        Ok(list)
+    }
+
+    fn parse_map(
+        &mut self,
+        keyword: Token,
+    ) -> Result<IndexMap<Cow<'i, str>, Value<'i>>, ParseError> {
+        let open = self.expect(TokenKind::OpenParen, "'('")?;
+        self.enter(keyword.start..open.end)?;
+
+        let mut map = IndexMap::new();
+        self.parse_items(|parser| {
+            let key = parser.next()?;
+            if !matches!(key.kind, TokenKind::QuotedString | TokenKind::RawString) {
+                return Err(parser.unexpected(key, &["map entry"]));
+            }
 
-fn parse_float(pair: Pair<'_, Rule>) -> Result<Value, ParseError<'_>> {
-    let s = pair.into_inner().next().ok_or(ParseError::Value)?.as_str();
-    let n: f64 = s.parse().map_err(|err: std::num::ParseFloatError| {
-        ParseError::Float(format!("value '{s}':\n{err}"))
-    })?;
-    Ok(Value::Float(n))
+            let text = parse_text(parser.input, key.range(), parser.options)?;
+            parser.expect(TokenKind::Colon, "':'")?;
+            let (value, _) = parser.parse_value()?;
+
+            insert_key(
+                &mut map,
+                text,
+                value,
+                parser.options,
+                parser.input,
+                key.range(),
+                "key",
+            )
+        })?;
+
+        self.depth -= 1;
+        Ok(map)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use Value::*;
+    use crate::options::DEFAULT_MAX_DEPTH;
+    use Value::{Int, Text};
+
+    // these shadow the functions being tested, so that with the `pest`
+    // feature every input is also checked against the grammar
+
+    fn parse_input(input: &str) -> Result<Vec<Block<'_>>, ParseError> {
+        parse_input_with(input, &ParseOptions::default())
+    }
+
+    fn parse_input_with<'i>(
+        input: &'i str,
+        options: &ParseOptions,
+    ) -> Result<Vec<Block<'i>>, ParseError> {
+        let result = super::parse_input_with(input, options);
+        #[cfg(feature = "pest")]
+        crate::grammar::tests::assert_same(
+            input,
+            &result,
+            &crate::grammar::parse_input_with(input, options),
+        );
+        result
+    }
+
+    fn parse_input_spanned(input: &str) -> Result<(Vec<Block<'_>>, SourceMap), ParseError> {
+        parse_input_spanned_with(input, &ParseOptions::default())
+    }
+
+    fn parse_input_spanned_with<'i>(
+        input: &'i str,
+        options: &ParseOptions,
+    ) -> Result<(Vec<Block<'i>>, SourceMap), ParseError> {
+        let result = super::parse_input_spanned_with(input, options);
+        #[cfg(feature = "pest")]
+        crate::grammar::tests::assert_same(
+            input,
+            &result,
+            &crate::grammar::parse_input_spanned_with(input, options),
+        );
+        result
+    }
 
     #[test]
     fn test_input_with_empty_blocks() {
@@ -135,8 +454,8 @@ mod tests {
         assert_eq!(
             block1.next(),
             Some(Block {
-                name: "Person".to_owned(),
-                fields: HashMap::default()
+                name: "Person".into(),
+                fields: IndexMap::default()
             })
         );
 
@@ -145,24 +464,24 @@ mod tests {
         assert_eq!(
             block2.next(),
             Some(Block {
-                name: "One".to_owned(),
-                fields: HashMap::default()
+                name: "One".into(),
+                fields: IndexMap::default()
             })
         );
 
         assert_eq!(
             block2.next(),
             Some(Block {
-                name: "Two".to_owned(),
-                fields: HashMap::default()
+                name: "Two".into(),
+                fields: IndexMap::default()
             })
         );
 
         assert_eq!(
             block2.next(),
             Some(Block {
-                name: "Three".to_owned(),
-                fields: HashMap::default()
+                name: "Three".into(),
+                fields: IndexMap::default()
             })
         );
 
@@ -181,10 +500,10 @@ mod tests {
         assert_eq!(
             iter.next(),
             Some(Block {
-                name: "App".to_string(),
-                fields: HashMap::from([
-                    ("isRunning".to_owned(), Value::Bool(false)),
-                    ("num".to_string(), Value::Int(64))
+                name: "App".into(),
+                fields: IndexMap::from([
+                    ("isRunning".into(), Value::Bool(false)),
+                    ("num".into(), Value::Int(64))
                 ])
             })
         );
@@ -192,6 +511,151 @@ mod tests {
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn test_field_order() {
+        let input = r#"App { zeta: int(1) alpha: int(2) mid: map("z": none, "a": none) }"#;
+        let (blocks, source_map) = parse_input_spanned(input).unwrap();
+
+        let keys: Vec<_> = blocks[0].get_fields().keys().collect();
+        assert_eq!(keys, ["zeta", "alpha", "mid"]);
+
+        let spans: Vec<_> = source_map.block(0).unwrap().get_fields().keys().collect();
+        assert_eq!(spans, ["zeta", "alpha", "mid"]);
+
+        let Some(Value::Map(map)) = blocks[0].get_fields().get("mid") else {
+            panic!("expected a map");
+        };
+        let keys: Vec<_> = map.keys().collect();
+        assert_eq!(keys, ["z", "a"]);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let input = "App {\n    name: text(\"a\")\n    name: text(\"b\")\n}";
+
+        let err = parse_input(input).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::DuplicateKey);
+        assert_eq!(err.message(), "duplicate field 'name'");
+        assert_eq!((err.line(), err.column()), (3, 5));
+
+        let err = parse_input(r#"App { m: map("k": none, r"k": none) }"#).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::DuplicateKey);
+        assert_eq!(err.message(), "duplicate key 'k'");
+        assert_eq!(err.range(), 24..28);
+
+        let options = ParseOptions::new().duplicate_keys(DuplicateKeys::LastWins);
+        let blocks = parse_input_with(input, &options).unwrap();
+        assert_eq!(blocks[0].fields["name"], Text("b".into()));
+
+        let options = ParseOptions::new().duplicate_keys(DuplicateKeys::FirstWins);
+        let (blocks, source_map) = parse_input_spanned_with(input, &options).unwrap();
+        assert_eq!(blocks[0].fields["name"], Text("a".into()));
+        assert_eq!(source_map.field(0, "name").unwrap().value.line, 2);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let input = "App { a: bool(on) b: bool(0) }";
+        let err = parse_input(input).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::Bool);
+        assert_eq!(err.expected(), ["true", "false", "yes", "no"]);
+
+        let options = ParseOptions::new().lenient_bools(true);
+        let blocks = parse_input_with(input, &options).unwrap();
+        assert_eq!(blocks[0].fields["a"], Value::Bool(true));
+        assert_eq!(blocks[0].fields["b"], Value::Bool(false));
+        assert!(parse_input_with("App { a: bool(2) }", &options).is_err());
+
+        let input = r#"App { l: list(int(1), int(2),) m: map("a": none,) }"#;
+        let err = parse_input(input).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::Syntax);
+        assert_eq!(err.message(), "trailing comma");
+        assert_eq!(err.range(), 28..29);
+
+        let options = ParseOptions::new().trailing_commas(true);
+        let blocks = parse_input_with(input, &options).unwrap();
+        assert_eq!(blocks[0].fields["l"], Value::List(vec![Int(1), Int(2)]));
+        assert!(parse_input_with("App { l: list(,) }", &options).is_err());
+
+        let input = "App { a: list(list(int(1))) b: Inner { c: map() } }";
+        let options = ParseOptions::new().max_depth(3);
+        assert!(parse_input_with(input, &options).is_ok());
+
+        let options = ParseOptions::new().max_depth(2);
+        let err = parse_input_with(input, &options).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::DepthLimit);
+        assert_eq!(err.range(), 14..19);
+
+        let options = ParseOptions::new().max_input_size(input.len());
+        assert!(parse_input_with(input, &options).is_ok());
+
+        let options = ParseOptions::new().max_input_size(10);
+        let err = parse_input_with(input, &options).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::InputSizeLimit);
+        assert_eq!(err.column(), 11);
+    }
+
+    #[test]
+    fn test_adversarial_inputs() {
+        let nested = |open: &str, close: &str, n: usize| {
+            format!("A {{ a: {}int(1){} }}", open.repeat(n), close.repeat(n))
+        };
+
+        // just within the default limit, as the block is also counted
+        let input = nested("list(", ")", DEFAULT_MAX_DEPTH - 1);
+        assert!(parse_input(&input).is_ok());
+
+        for input in [
+            nested("list(", ")", 1_000_000),
+            nested("map(\"k\": ", ")", 1_000_000),
+            nested("B { b: ", " }", 1_000_000),
+            nested("list #(\n (", ")", 1_000_000),
+            nested("list(text(\")\"), text(r#\"(\"#), ", ")", 1_000_000),
+        ] {
+            let err = parse_input(&input).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::DepthLimit);
+            assert_eq!(err.message(), "nesting is deeper than the limit of 128");
+        }
+
+        // brackets inside of text and comments are not counted
+        let input = format!(
+            "A {{ a: text(\"{0}\") b: text(r#\"{0}\"#) # {0}\n }}",
+            "(".repeat(1_000)
+        );
+        assert!(parse_input(&input).is_ok());
+
+        let input = format!("A {{ a: list({}) }}", ["int(1)"; 100_000].join(", "));
+        let options = ParseOptions::new().max_values(1_000);
+        let err = parse_input_with(&input, &options).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::ValueCountLimit);
+
+        let input = "A {} ".repeat(100_000);
+        let err = parse_input_with(&input, &options).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::ValueCountLimit);
+        assert_eq!(err.range(), 5_000..5_004);
+
+        let long = "a".repeat(100_000);
+        let options = ParseOptions::new().max_text_length(1_000);
+        for input in [
+            format!("A {{ a: text(\"{long}\") }}"),
+            format!("A {{ a: text(r\"{long}\") }}"),
+            format!("A {{ a: map(\"{long}\": none) }}"),
+        ] {
+            let err = parse_input_with(&input, &options).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::TextLengthLimit);
+        }
+
+        // escapes are resolved before the length is checked
+        let input = format!("A {{ a: text(\"{}\") }}", r"\u{1F600}".repeat(250));
+        assert!(parse_input_with(&input, &options).is_ok());
+
+        let input = format!("A {{ a: text(\"{}\") }}", "\u{e9}".repeat(1_000));
+        let options = ParseOptions::new().max_input_size(1_000);
+        let err = parse_input_with(&input, &options).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::InputSizeLimit);
+        assert_eq!(err.range(), 999..999);
+    }
+
     #[test]
     fn test_list() {
         let input = r#"NumList { arr: list(int(1), int(2), int(3)) }"#;
@@ -204,44 +668,229 @@ mod tests {
         assert_eq!(
             iter.next(),
             Some(Block {
-                name: "NumList".to_string(),
-                fields: HashMap::from([(
-                    "arr".to_string(),
-                    Value::List(vec![Int(1), Int(2), Int(3)])
-                )])
+                name: "NumList".into(),
+                fields: IndexMap::from([("arr".into(), Value::List(vec![Int(1), Int(2), Int(3)]))])
             })
         );
     }
 
+    /// Parses a single value.
+    fn value(input: &str) -> Result<Value<'_>, ParseError> {
+        let options = ParseOptions::default();
+        let mut parser = Parser::new(input, &options, false)?;
+        let (value, _) = parser.parse_value()?;
+        parser.expect(TokenKind::Eof, "end of input")?;
+        Ok(value)
+    }
+
     #[test]
     fn test_numbers() {
         let input = "int(30493093094)";
         let input2 = "int(99999999999999999999999999999999)";
 
-        let p = SirtParser::parse(Rule::int, input).unwrap().next().unwrap();
-        let p2 = SirtParser::parse(Rule::int, input2)
-            .unwrap()
-            .next()
-            .unwrap();
-
-        assert_eq!(parse_int(p), Ok(Value::Int(30493093094)));
-        assert!(parse_int(p2).is_err());
+        assert_eq!(value(input), Ok(Value::Int(30493093094)));
+        assert_eq!(value(input2).unwrap_err().kind(), ParseErrorKind::Int);
     }
 
     #[test]
     fn test_floats() {
-        let input = "float(12.0)";
-        let input2 = "float(0.)";
-        let parser = SirtParser::parse(Rule::float, input);
-        let parser2 = SirtParser::parse(Rule::float, input2);
+        assert_eq!(value("float(12.0)"), Ok(Value::Float(12.0)));
+        assert_eq!(value("float(0.)"), Ok(Value::Float(0.0)))
+    }
 
-        assert!(parser.is_ok());
-        assert!(parser2.is_ok());
+    #[test]
+    fn test_float_syntax() {
+        let parse = |input| value(input).map_err(|e| e.kind());
+
+        assert_eq!(parse("float(-2.5)"), Ok(Value::Float(-2.5)));
+        assert_eq!(parse("float(1e300)"), Ok(Value::Float(1e300)));
+        assert_eq!(parse("float(2.5E-3)"), Ok(Value::Float(2.5e-3)));
+        assert_eq!(parse("float(inf)"), Ok(Value::Float(f64::INFINITY)));
+        assert_eq!(parse("float(-inf)"), Ok(Value::Float(f64::NEG_INFINITY)));
+        assert!(matches!(parse("float(nan)"), Ok(Value::Float(n)) if n.is_nan()));
+
+        assert_eq!(parse("float(1e400)"), Err(ParseErrorKind::Float));
+        assert_eq!(parse("float(1)"), Err(ParseErrorKind::Syntax));
+        assert_eq!(parse("float(e5)"), Err(ParseErrorKind::Syntax));
+    }
+
+    #[test]
+    fn test_text_escapes() {
+        let input = r#"Text { a: text("say \"hi\"\n\tC:\\ \u{1F980}") b: text("\q") }"#;
+        assert_eq!(
+            parse_input(input).unwrap_err().kind(),
+            ParseErrorKind::Syntax
+        );
+
+        let input = r#"Text { a: text("say \"hi\"\n\tC:\\ \u{1F980}") }"#;
+        let block = parse_input(input).unwrap().remove(0);
+
+        assert_eq!(
+            block.fields.get("a"),
+            Some(&Text("say \"hi\"\n\tC:\\ \u{1F980}".into()))
+        );
+
+        let input = r#"Text { a: text("\u{D800}") }"#;
+        assert_eq!(parse_input(input).unwrap_err().kind(), ParseErrorKind::Text);
+    }
+
+    #[test]
+    fn test_raw_text() {
+        let input = r##"Raw { a: text(r"C:\path\n") b: text(r#"say "hi""#) }"##;
+        let block = parse_input(input).unwrap().remove(0);
+
+        assert_eq!(block.fields.get("a"), Some(&Text(r"C:\path\n".into())));
+        assert_eq!(block.fields.get("b"), Some(&Text(r#"say "hi""#.into())));
+    }
+
+    #[test]
+    fn test_borrowed_text() {
+        let input =
+            r#"App { a: text("plain") b: text(r"C:\path") c: text("a\tb") d: map("k\n": none) }"#;
+        let block = parse_input(input).unwrap().remove(0);
+        let borrowed = |value: &Value| matches!(value, Text(Cow::Borrowed(_)));
+
+        assert!(matches!(block.name, Cow::Borrowed("App")));
+        assert!(
+            block
+                .fields
+                .keys()
+                .all(|key| matches!(key, Cow::Borrowed(_)))
+        );
+        assert!(borrowed(&block.fields["a"]));
+        assert!(borrowed(&block.fields["b"]));
+        assert!(!borrowed(&block.fields["c"]));
+
+        let Value::Map(map) = &block.fields["d"] else {
+            panic!("expected a map");
+        };
+        assert!(matches!(map.keys().next(), Some(Cow::Owned(key)) if key == "k\n"));
+    }
+
+    #[test]
+    fn test_nested_block() {
+        let input = r#"App { window: Window { width: int(800) size: Size { h: int(2) } } }"#;
+        let block = parse_input(input).unwrap().remove(0);
+
+        assert_eq!(
+            block.fields.get("window"),
+            Some(&Value::Block(Block {
+                name: "Window".into(),
+                fields: IndexMap::from([
+                    ("width".into(), Int(800)),
+                    (
+                        "size".into(),
+                        Value::Block(Block {
+                            name: "Size".into(),
+                            fields: IndexMap::from([("h".into(), Int(2))])
+                        })
+                    )
+                ])
+            }))
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        let input = r#"Env { vars: map("PATH": text("/bin"), "a \"b\"": int(1)) empty: map() }"#;
+        let block = parse_input(input).unwrap().remove(0);
+
+        assert_eq!(
+            block.fields.get("vars"),
+            Some(&Value::Map(IndexMap::from([
+                ("PATH".into(), Text("/bin".into())),
+                ("a \"b\"".into(), Int(1))
+            ])))
+        );
+        assert_eq!(
+            block.fields.get("empty"),
+            Some(&Value::Map(IndexMap::new()))
+        );
+    }
+
+    #[test]
+    fn test_none() {
+        let input =
+            "Opt { a: none b: list(none, int(1)) none_block: none_b { } named: none { a: none } }";
+        let block = parse_input(input).unwrap().remove(0);
+
+        assert_eq!(block.fields.get("a"), Some(&Value::None));
+        assert_eq!(
+            block.fields.get("b"),
+            Some(&Value::List(vec![Value::None, Int(1)]))
+        );
+        assert!(matches!(
+            block.fields.get("none_block"),
+            Some(Value::Block(_))
+        ));
+
+        let Some(Value::Block(named)) = block.fields.get("named") else {
+            panic!("expected a block named none");
+        };
+        assert_eq!(named.name, "none");
+        assert_eq!(named.fields.get("a"), Some(&Value::None));
+    }
+
+    #[test]
+    fn test_spans() {
+        let input =
+            "# config\nApp {\n    name: text(\"é\")\n  window: Window { width: int(800) }\n}\n";
+        let (blocks, source_map) = parse_input_spanned(input).unwrap();
+
+        assert_eq!(blocks.len(), 1);
+
+        let app = source_map.block(0).unwrap();
+        assert_eq!((app.name.line, app.name.column), (2, 1));
+        assert_eq!(&input[app.name.range()], "App");
+        assert_eq!(&input[app.span.range()], &input[9..input.len() - 1]);
+
+        let name = source_map.field(0, "name").unwrap();
+        assert_eq!((name.key.line, name.key.column), (3, 5));
+        assert_eq!((name.value.line, name.value.column), (3, 11));
+        assert_eq!(&input[name.value.range()], "text(\"é\")");
+        assert!(name.block.is_none());
+
+        let window = source_map.field(0, "window").unwrap();
+        let width = window.block.as_ref().unwrap().field("width").unwrap();
+        assert_eq!((width.value.line, width.value.column), (4, 27));
+        assert_eq!(&input[width.value.range()], "int(800)");
+
+        assert!(source_map.block(1).is_none());
+    }
+
+    #[test]
+    fn test_located_errors() {
+        let input = "App {\n    name: text(\"a\")\n    port: int(80\n}";
+        let err = parse_input(input).unwrap_err();
+
+        assert_eq!(err.kind(), ParseErrorKind::Syntax);
+        assert_eq!((err.line(), err.column()), (3, 11));
+        assert_eq!(err.range(), 36..36);
+        assert!(err.expected().contains(&"int(...)".into()));
+        assert_eq!(err.snippet(), "  |\n3 |     port: int(80\n  |           ^");
+
+        let input = "App {\n  on: bool(maybe)\n}";
+        let err = parse_input(input).unwrap_err();
+
+        assert_eq!(err.kind(), ParseErrorKind::Bool);
+        assert_eq!((err.line(), err.column()), (2, 12));
+        assert_eq!(&input[err.range()], "maybe");
+        assert_eq!(
+            err.snippet(),
+            "  |\n2 |   on: bool(maybe)\n  |            ^^^^^"
+        );
+        assert_eq!(err.expected(), ["true", "false", "yes", "no"]);
+
+        let input = r#"App { a: text("ok \u{D800}") }"#;
+        let err = parse_input(input).unwrap_err();
+
+        assert_eq!(err.kind(), ParseErrorKind::Text);
+        assert_eq!(&input[err.range()], r"\u{D800}");
 
-        let p = parser.unwrap().into_iter().next().unwrap();
-        let p2 = parser2.unwrap().into_iter().next().unwrap();
+        let input = "App { n: int(99999999999999999999) }";
+        let err = parse_input(input).unwrap_err();
 
-        assert_eq!(parse_float(p), Ok(Value::Float(12.0)));
-        assert_eq!(parse_float(p2), Ok(Value::Float(0.0)))
+        assert_eq!(err.kind(), ParseErrorKind::Int);
+        assert_eq!(&input[err.range()], "99999999999999999999");
     }
 }
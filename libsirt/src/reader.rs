@@ -0,0 +1,360 @@
+//! Reading blocks one at a time, for input that is
+//! too large to be read into memory at once.
+
+use crate::error::Error;
+use crate::lexer::{Lexer, TokenKind};
+use crate::options::ParseOptions;
+use crate::{Block, parse_input_with};
+
+use std::io::{BufRead, Read};
+
+/// How many characters of the line before a block are kept,
+/// so that an error snippet can show where the block starts
+/// on a long line without keeping the whole line.
+const MAX_LINE_PREFIX: usize = 80;
+
+/// An iterator over the blocks of a [BufRead], which only
+/// holds the block that is being read in memory.
+///
+/// The options are applied to each block on its own, so
+/// [ParseOptions::max_input_size] limits the size of a
+/// single block rather than of the whole input.
+///
+/// The iterator stops after the first error.
+///
+/// ```
+/// use libsirt::BlockReader;
+///
+/// let input = "A { x: int(1) }\nB { y: int(2) }";
+/// let names: Vec<String> = BlockReader::new(input.as_bytes())
+///     .map(|block| block.unwrap().get_name().to_string())
+///     .collect();
+///
+/// assert_eq!(names, ["A", "B"]);
+/// ```
+pub struct BlockReader<R> {
+    reader: R,
+    options: ParseOptions,
+    /// Input that has been read, starting after the
+    /// last block that was returned.
+    buffer: String,
+    /// How far the buffer has been split into tokens.
+    lexed: usize,
+    /// How many braces are open at `lexed`.
+    depth: usize,
+    /// The location of the start of the buffer in the input.
+    offset: usize,
+    line: usize,
+    /// How many characters of the line that the buffer starts
+    /// on come before the buffer.
+    column: usize,
+    /// The text of the line that the buffer starts on, before
+    /// the buffer, so that errors can show the line, which is
+    /// cut to its last [MAX_LINE_PREFIX] characters.
+    line_prefix: String,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: BufRead> BlockReader<R> {
+    /// Reads blocks with the default [ParseOptions].
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, &ParseOptions::default())
+    }
+
+    /// Reads blocks with the given [ParseOptions].
+    pub fn with_options(reader: R, options: &ParseOptions) -> Self {
+        Self {
+            reader,
+            options: options.clone(),
+            buffer: String::new(),
+            lexed: 0,
+            depth: 0,
+            offset: 0,
+            line: 1,
+            column: 0,
+            line_prefix: String::new(),
+            eof: false,
+            done: false,
+        }
+    }
+
+    fn read_block(&mut self) -> Result<Option<Block<'static>>, Error> {
+        loop {
+            if let Some(end) = self.find_end() {
+                return self.parse(end);
+            }
+
+            if self.eof {
+                return self.parse(self.buffer.len());
+            }
+
+            self.read()?;
+
+            self.locate(self.options.check_input_size(&self.buffer))?;
+        }
+    }
+
+    /// Reads up to and including the next `}`, which can't be
+    /// in the middle of a character or of a token other than
+    /// text or a comment.
+    ///
+    /// No more than one byte past [ParseOptions::max_input_size]
+    /// is read, so that a long run without a `}` is rejected
+    /// before all of it is in memory.
+    fn read(&mut self) -> Result<(), Error> {
+        let limit = match self.options.get_max_input_size() {
+            Some(max) => max.saturating_sub(self.buffer.len()) as u64 + 1,
+            None => u64::MAX,
+        };
+
+        let mut bytes = Vec::new();
+        let read = (&mut self.reader)
+            .take(limit)
+            .read_until(b'}', &mut bytes)?;
+        // a read that stops at the limit is rejected as too large
+        self.eof = bytes.last() != Some(&b'}') && (read as u64) < limit;
+
+        let text = std::str::from_utf8(&bytes)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        self.buffer.push_str(text);
+        Ok(())
+    }
+
+    /// Returns where the first block in the buffer ends, if
+    /// all of it has been read, or where the parser should
+    /// stop to report an error.
+    fn find_end(&mut self) -> Option<usize> {
+        let mut lexer = Lexer::starting_at(&self.buffer, self.lexed);
+
+        loop {
+            let token = match lexer.next_token() {
+                Ok(token) => token,
+                // text or a comment may be cut off by the end of what was read
+                Err(err) if !self.eof && err.range().end >= self.buffer.len() => return None,
+                Err(_) => return Some(self.buffer.len()),
+            };
+
+            match token.kind {
+                TokenKind::Eof => return None,
+                TokenKind::OpenBrace => self.depth += 1,
+                TokenKind::CloseBrace if self.depth <= 1 => {
+                    self.lexed = token.end;
+                    return Some(token.end);
+                }
+                TokenKind::CloseBrace => self.depth -= 1,
+                _ => {}
+            }
+
+            self.lexed = token.end;
+        }
+    }
+
+    /// Parses the buffer up to `end`, and removes it from the buffer.
+    fn parse(&mut self, end: usize) -> Result<Option<Block<'static>>, Error> {
+        let blocks = parse_input_with(&self.buffer[..end], &self.options);
+        let block = self
+            .locate(blocks)?
+            .into_iter()
+            .next()
+            .map(Block::into_owned);
+
+        let consumed = &self.buffer[..end];
+        let line = match consumed.rfind('\n') {
+            Some(i) => {
+                self.line += consumed.matches('\n').count();
+                self.column = 0;
+                self.line_prefix.clear();
+                &consumed[i + 1..]
+            }
+            None => consumed,
+        };
+        self.column += line.chars().count();
+        self.line_prefix.push_str(line);
+
+        let extra = self
+            .line_prefix
+            .chars()
+            .count()
+            .saturating_sub(MAX_LINE_PREFIX);
+        if let Some((cut, _)) = self.line_prefix.char_indices().nth(extra) {
+            self.line_prefix.drain(..cut);
+        }
+
+        self.offset += end;
+        self.buffer.drain(..end);
+        self.lexed = 0;
+        self.depth = 0;
+
+        Ok(block)
+    }
+
+    /// Moves the location of an error in the buffer to its
+    /// location in the whole input.
+    fn locate<T>(&self, result: Result<T, crate::error::ParseError>) -> Result<T, Error> {
+        result.map_err(|err| {
+            let prefix = if self.column > self.line_prefix.chars().count() {
+                format!("...{}", self.line_prefix)
+            } else {
+                self.line_prefix.clone()
+            };
+
+            err.offset(self.offset, self.line, self.column, &prefix)
+                .into()
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for BlockReader<R> {
+    type Item = Result<Block<'static>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let block = self.read_block().transpose();
+        self.done = !matches!(block, Some(Ok(_)));
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorKind;
+    use std::io::BufReader;
+
+    /// Reads the input a byte at a time, so that text and
+    /// comments are cut off as often as possible.
+    fn read(input: &str, options: &ParseOptions) -> Vec<Result<Block<'static>, Error>> {
+        let reader = BufReader::with_capacity(1, input.as_bytes());
+        BlockReader::with_options(reader, options).collect()
+    }
+
+    #[test]
+    fn test_same_as_parse_input() {
+        let input = r##"
+        # a comment with a } in it
+        A { a: text("}") b: text(r#"{ "}" "#) }  B {} C { c: C { d: map("}": list(int(1))) } }
+        D {
+            # }
+            e: none
+        }
+        "##;
+
+        let blocks: Vec<_> = read(input, &ParseOptions::default())
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            blocks,
+            parse_input_with(input, &ParseOptions::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let options = ParseOptions::default();
+
+        for input in [
+            "A {} B { b: int(x) }",
+            "A {}\n\n  B {\n b: int(1)\n c: ☃ }",
+            "A {} \u{e9}B { b: text(\"\\q\") }",
+            "A {}\nB { b: int(1)",
+            "A {}\nB { b: text(\"}",
+            "A {}\n}",
+            "A {} B",
+        ] {
+            let expected = parse_input_with(input, &options).unwrap_err();
+            let mut blocks = read(input, &options).into_iter();
+
+            assert!(blocks.next().unwrap().is_ok(), "{input}");
+            let Some(Err(Error::Parse(err))) = blocks.next() else {
+                panic!("expected a parse error for {input}");
+            };
+            assert_eq!(err.kind(), expected.kind(), "{input}");
+            assert_eq!(err.span(), expected.span(), "{input}");
+            assert_eq!(err.snippet(), expected.snippet(), "{input}");
+            assert!(blocks.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_error_snippet() {
+        let input = "A { a: int(1) }\n  B {} C { c: int(x) }";
+        let mut blocks = read(input, &ParseOptions::default()).into_iter().skip(2);
+
+        let Some(Err(Error::Parse(err))) = blocks.next() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!((err.line(), err.column()), (2, 15));
+        assert_eq!(
+            err.snippet(),
+            "  |\n2 |   B {} C { c: int(x) }\n  |               ^"
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let input = "A { a: text(\"1234\") }\nB { b: text(\"12345678\") }";
+
+        let options = ParseOptions::new().max_input_size(24);
+        let blocks = read(input, &options);
+        assert!(blocks[0].is_ok());
+
+        let Err(Error::Parse(err)) = &blocks[1] else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(err.kind(), ParseErrorKind::InputSizeLimit);
+        assert_eq!(err.line(), 2);
+    }
+
+    #[test]
+    fn test_one_line() {
+        let mut input = "A {} ".repeat(1000);
+        input.push_str("B { b: int(x) }");
+
+        let mut reader = BlockReader::new(input.as_bytes());
+        for _ in 0..1000 {
+            assert!(reader.next().unwrap().is_ok());
+            assert!(reader.line_prefix.len() <= MAX_LINE_PREFIX);
+        }
+
+        let Some(Err(Error::Parse(err))) = reader.next() else {
+            panic!("expected a parse error");
+        };
+        let expected = parse_input_with(&input, &ParseOptions::default()).unwrap_err();
+        assert_eq!(err.span(), expected.span());
+
+        let line = err.snippet().lines().nth(1).unwrap();
+        assert!(line.starts_with("1 | ... A {} "), "{line}");
+        assert!(line.ends_with(" A {} B { b: int(x) }"), "{line}");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_size_limit_while_reading() {
+        // a run without a `}` that never ends
+        let reader = BufReader::new(std::io::repeat(b' '));
+        let options = ParseOptions::new().max_input_size(100);
+        let mut blocks = BlockReader::with_options(reader, &options);
+
+        let Some(Err(Error::Parse(err))) = blocks.next() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(err.kind(), ParseErrorKind::InputSizeLimit);
+        assert!(blocks.buffer.len() <= 101);
+        assert!(blocks.next().is_none());
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let input = b"A {} B { b: text(\"\xff\") }";
+        let mut blocks = BlockReader::new(&input[..]);
+
+        assert!(blocks.next().unwrap().is_ok());
+        assert!(matches!(blocks.next(), Some(Err(Error::Io(_)))));
+        assert!(blocks.next().is_none());
+    }
+}
@@ -1,51 +1,328 @@
-use crate::error::SirtSerializeError;
+use crate::de::loosely_matches;
+use crate::error::{Error, PathSegment, SirtSerializeError};
+use crate::types::Int;
+use crate::value::BLOCK_TOKEN;
 use serde::{
     Serialize,
-    ser::{self, Impossible, SerializeSeq, SerializeStruct},
+    ser::{
+        self, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+    },
 };
+use std::fmt;
+use std::io;
 
-pub struct SirtSerializer {
-    output: String,
+/// What the serializer writes when it is outside of a block.
+#[derive(Debug, Clone)]
+enum Root<'n> {
+    /// A single block with the given name.
+    Block(&'n str),
+    /// A document, from a struct or map whose fields
+    /// hold blocks, or from a list of blocks.
+    Document,
+    /// The blocks held by a field of a document, which is
+    /// omitted if it is `None`, or a block for each element
+    /// if it is a list.
+    Field(String),
+    /// A block named after its struct.
+    Struct,
+    /// A single value, as written in a field.
+    Value,
+}
+
+pub struct SirtSerializer<'n, W> {
+    output: W,
     prettify: bool,
+    depth: usize,
+    root: Root<'n>,
+    /// How many top-level blocks have been written.
+    blocks: usize,
+    /// The name of the last top-level block that was opened.
+    block: Option<String>,
+    /// Whether the next map holds the fields of the block
+    /// that was just opened, as for a [crate::Block].
+    inline_fields: bool,
+    /// Whether names that are not identifiers are an error,
+    /// rather than written as they are.
+    check_names: bool,
+}
+
+/// Converts the name of a field into the name of a block,
+/// such as `dev_dependency` into `DevDependency`.
+pub(crate) fn pascal_case(field: &str) -> String {
+    field
+        .split('_')
+        .flat_map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
+
+/// Adapts an [io::Write] into a [fmt::Write], keeping the
+/// error that made a write fail so it can be returned.
+struct IoWriter<W> {
+    writer: W,
+    error: Option<io::Error>,
 }
 
-impl SirtSerializer {
-    fn new(name: &str, prettify: bool) -> Self {
-        let mut output = String::new();
-        output.push_str(&format!("{name} {{ "));
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+fn write_failed(_: fmt::Error) -> SirtSerializeError {
+    ser::Error::custom("failed to write the output")
+}
+
+impl<'n, W: fmt::Write> SirtSerializer<'n, W> {
+    fn new(output: W, root: Root<'n>, prettify: bool) -> Self {
+        Self {
+            output,
+            prettify,
+            depth: 0,
+            root,
+            blocks: 0,
+            block: None,
+            inline_fields: false,
+            check_names: true,
+        }
+    }
+
+    fn write_str(&mut self, s: &str) -> Result<(), SirtSerializeError> {
+        self.output.write_str(s).map_err(write_failed)
+    }
+
+    /// Allows the use of `write!` on the serializer.
+    fn write_fmt(&mut self, args: fmt::Arguments) -> Result<(), SirtSerializeError> {
+        self.output.write_fmt(args).map_err(write_failed)
+    }
+
+    /// Writes a string so that it can appear between the
+    /// quotes of a `text(...)` value, or of a map key.
+    fn write_escaped(&mut self, s: &str) -> Result<(), SirtSerializeError> {
+        let mut start = 0;
+
+        for (i, c) in s.char_indices() {
+            if c != '"' && c != '\\' && !c.is_control() {
+                continue;
+            }
+
+            self.write_str(&s[start..i])?;
+            start = i + c.len_utf8();
+
+            match c {
+                '"' => self.write_str("\\\"")?,
+                '\\' => self.write_str("\\\\")?,
+                '\n' => self.write_str("\\n")?,
+                '\t' => self.write_str("\\t")?,
+                '\r' => self.write_str("\\r")?,
+                '\0' => self.write_str("\\0")?,
+                c => write!(self, "\\u{{{:x}}}", c as u32)?,
+            }
+        }
+
+        self.write_str(&s[start..])
+    }
+
+    /// Writes the header of a top-level block, named after the
+    /// struct it is written from if there is one.
+    ///
+    /// In a field of a document, the struct name is only used
+    /// if it matches the name of the field, so that the block
+    /// is read back into the same field.
+    fn open_block(&mut self, struct_name: Option<&str>) -> Result<(), SirtSerializeError> {
+        let name = match (&self.root, struct_name) {
+            (Root::Block(name), _) => name.to_string(),
+            (Root::Field(field), Some(name)) if loosely_matches(field, name) => name.to_string(),
+            (Root::Field(field), _) => pascal_case(field),
+            (Root::Struct | Root::Value, Some(name)) => name.to_string(),
+            (Root::Struct | Root::Document | Root::Value, None) => {
+                return Err(ser::Error::custom(
+                    "a map can only be serialized as a block if it has a name",
+                ));
+            }
+            // only a [crate::Block] names itself as a document
+            (Root::Document, Some(name)) => name.to_string(),
+        };
+
+        self.check_name(&name, "block")?;
+
+        if self.blocks > 0 {
+            self.write_str("\n")?;
+        }
+        write!(self, "{name} {{")?;
+        self.block = Some(name);
+        Ok(())
+    }
+
+    /// Opens a block named after a struct, which is
+    /// nested if the struct is inside of a field.
+    fn begin_struct(&mut self, name: &str) -> Result<(), SirtSerializeError> {
+        if self.nested() {
+            self.check_name(name, "block")?;
+            write!(self, "{name} {{")?;
+        } else {
+            self.open_block(Some(name))?;
+        }
+        self.separator()?;
+        self.depth += 1;
+        Ok(())
+    }
 
-        if prettify {
-            output.push('\n');
+    /// Checks that `name` can be read back as the name
+    /// of a block or field.
+    fn check_name(&self, name: &str, of: &str) -> Result<(), SirtSerializeError> {
+        if self.check_names && !is_ident(name) {
+            return Err(ser::Error::custom(format!(
+                "'{name}' cannot be used as the name of a {of}"
+            )));
         }
 
-        Self { output, prettify }
+        Ok(())
     }
 
-    fn complete(mut self) -> String {
-        self.output
-            .push_str(&format!("}}{}", if self.prettify { "\n" } else { "" }));
-        self.output
+    fn close_block(&mut self) -> Result<(), SirtSerializeError> {
+        self.write_str("}")?;
+        self.pretty_newline()?;
+        self.blocks += 1;
+        Ok(())
     }
 
-    fn pretty_newline(&mut self) {
+    /// Writes the blocks of a document from `value`, recording
+    /// the name of the block in the path of any error.
+    fn serialize_blocks<T>(
+        &mut self,
+        root: Root<'n>,
+        value: &T,
+        index: Option<usize>,
+    ) -> Result<(), SirtSerializeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let fallback = match &root {
+            Root::Field(field) => Some(pascal_case(field)),
+            _ => None,
+        };
+
+        let parent = std::mem::replace(&mut self.root, root);
+        self.block = None;
+
+        let result = value.serialize(&mut *self).map_err(|err| {
+            let err = match index {
+                Some(index) => err.prepend(PathSegment::Index(index)),
+                None => err,
+            };
+
+            match self.block.take().or(fallback) {
+                Some(name) => err.prepend(PathSegment::Block(name)),
+                None => err,
+            }
+        });
+
+        self.root = parent;
+        result
+    }
+
+    /// Whether a list or `None` outside of a block holds the
+    /// blocks of a document, rather than being an error.
+    fn in_document(&self) -> bool {
+        self.depth == 0 && matches!(self.root, Root::Document | Root::Field(_))
+    }
+
+    fn pretty_newline(&mut self) -> Result<(), SirtSerializeError> {
         if self.prettify {
-            self.output.push('\n');
+            self.write_str("\n")?;
         }
+        Ok(())
     }
 
-    fn pretty_indent(&mut self, n: usize) {
+    fn pretty_indent(&mut self, n: usize) -> Result<(), SirtSerializeError> {
         if self.prettify {
-            self.output.push_str(&"\t".repeat(n));
+            for _ in 0..n {
+                self.write_str("\t")?;
+            }
         }
+        Ok(())
+    }
+
+    /// Writes a value, which can only appear inside
+    /// of a block.
+    fn push_value(&mut self, value: fmt::Arguments) -> Result<(), SirtSerializeError> {
+        self.check_nested()?;
+        self.write_fmt(value)
+    }
+
+    /// Whether the serializer is inside of a block, or is
+    /// writing a single value that needs no block.
+    fn nested(&self) -> bool {
+        self.depth > 0 || matches!(self.root, Root::Value)
+    }
+
+    fn check_nested(&self) -> Result<(), SirtSerializeError> {
+        if !self.nested() {
+            return Err(ser::Error::custom(
+                "only a struct or a map can be serialized as a block",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Starts an element of a list or an entry of a map.
+    fn begin_item(&mut self, first: bool) -> Result<(), SirtSerializeError> {
+        if !first {
+            self.write_str(if self.prettify { "," } else { ", " })?;
+        }
+
+        self.pretty_newline()?;
+        self.pretty_indent(self.depth)
+    }
+
+    /// Closes a list or a map.
+    fn end_items(&mut self, first: bool) -> Result<(), SirtSerializeError> {
+        self.depth -= 1;
+        if !first {
+            self.pretty_newline()?;
+            self.pretty_indent(self.depth)?;
+        }
+        self.write_str(")")
+    }
+
+    /// Opens the single entry map that holds the
+    /// data of an enum variant.
+    fn begin_variant(&mut self, variant: &str) -> Result<(), SirtSerializeError> {
+        self.push_value(format_args!("map("))?;
+        self.depth += 1;
+        self.begin_item(true)?;
+        self.write_str("\"")?;
+        self.write_escaped(variant)?;
+        self.write_str("\": ")
+    }
+
+    /// Ends a field or the opening of a block, either
+    /// with a newline or with a space.
+    fn separator(&mut self) -> Result<(), SirtSerializeError> {
+        self.write_str(if self.prettify { "\n" } else { " " })
     }
 }
 
-pub struct SirtListSerializer<'a> {
-    ser: &'a mut SirtSerializer,
-    first: bool,
+pub struct SirtListSerializer<'a, 'n, W> {
+    ser: &'a mut SirtSerializer<'n, W>,
+    index: usize,
+    /// Whether each element is a block of a document.
+    blocks: bool,
 }
 
-impl<'a> SerializeSeq for SirtListSerializer<'a> {
+impl<'a, 'n, W: fmt::Write> SerializeSeq for SirtListSerializer<'a, 'n, W> {
     type Ok = ();
     type Error = SirtSerializeError;
 
@@ -53,104 +330,297 @@ impl<'a> SerializeSeq for SirtListSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        if !self.first {
-            self.ser.output.push_str(", ");
-            self.ser.pretty_newline();
+        let index = self.index;
+        self.index += 1;
+
+        if self.blocks {
+            // the blocks of a field are named by the field, which
+            // also records the name in the path of an error
+            return match self.ser.root {
+                Root::Document => self.ser.serialize_blocks(Root::Struct, value, Some(index)),
+                _ => value
+                    .serialize(&mut *self.ser)
+                    .map_err(|err| err.prepend(PathSegment::Index(index))),
+            };
         }
-        self.first = false;
 
-        self.ser.pretty_indent(2);
-        value.serialize(&mut *self.ser)?;
-        Ok(())
+        self.ser.begin_item(index == 0)?;
+
+        value
+            .serialize(&mut *self.ser)
+            .map_err(|err| err.prepend(PathSegment::Index(index)))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.ser.pretty_newline();
-        self.ser.pretty_indent(1);
-        self.ser.output.push(')');
+        if !self.blocks {
+            self.ser.end_items(self.index == 0)?;
+        }
         Ok(())
     }
 }
 
-pub struct SirtStructSerializer<'a> {
-    ser: &'a mut SirtSerializer,
+impl<'a, 'n, W: fmt::Write> SerializeTuple for SirtListSerializer<'a, 'n, W> {
+    type Ok = ();
+    type Error = SirtSerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
 }
 
-impl<'a> SerializeStruct for SirtStructSerializer<'a> {
+impl<'a, 'n, W: fmt::Write> SerializeTupleStruct for SirtListSerializer<'a, 'n, W> {
     type Ok = ();
     type Error = SirtSerializeError;
 
-    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        self.ser.pretty_indent(1);
-        self.ser.output.push_str(&format!("{key}: "));
-        value.serialize(&mut *self.ser)?;
-        self.ser.pretty_newline();
-        self.ser.output.push(' ');
-        Ok(())
+        SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        SerializeSeq::end(self)
     }
 }
 
-impl<'a> ser::Serializer for &'a mut SirtSerializer {
+impl<'a, 'n, W: fmt::Write> SerializeTupleVariant for SirtListSerializer<'a, 'n, W> {
+    type Ok = ();
     type Error = SirtSerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        // closes the list, then the map holding the variant
+        self.ser.end_items(self.index == 0)?;
+        self.ser.end_items(false)
+    }
+}
+
+pub struct SirtMapSerializer<'a, 'n, W> {
+    ser: &'a mut SirtSerializer<'n, W>,
+    first: bool,
+    fields: bool,
+    /// Whether each entry holds the blocks of a document.
+    document: bool,
+    /// Whether the entries are the fields of a [crate::Block],
+    /// which closes the block itself.
+    inline: bool,
+    key: String,
+}
+
+impl<'a, 'n, W: fmt::Write> SerializeMap for SirtMapSerializer<'a, 'n, W> {
     type Ok = ();
-    type SerializeStruct = SirtStructSerializer<'a>;
-    type SerializeSeq = SirtListSerializer<'a>;
-    type SerializeMap = Impossible<(), SirtSerializeError>;
-    type SerializeStructVariant = Impossible<(), SirtSerializeError>;
-    type SerializeTuple = Impossible<(), SirtSerializeError>;
-    type SerializeTupleStruct = Impossible<(), SirtSerializeError>;
-    type SerializeTupleVariant = Impossible<(), SirtSerializeError>;
+    type Error = SirtSerializeError;
 
-    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(&format!("bool({v})"));
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = key.serialize(MapKeySerializer)?;
+
+        if self.document {
+            self.key = key;
+            return Ok(());
+        }
+
+        if self.fields {
+            self.ser.check_name(&key, "field")?;
+            self.ser.pretty_indent(self.ser.depth)?;
+            write!(self.ser, "{key}: ")?;
+            self.key = key;
+            return Ok(());
+        }
+
+        self.ser.begin_item(self.first)?;
+        self.first = false;
+
+        self.ser.write_str("\"")?;
+        self.ser.write_escaped(&key)?;
+        self.ser.write_str("\": ")?;
+        self.key = key;
         Ok(())
     }
 
-    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(&format!("int({v})"));
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.document {
+            let key = std::mem::take(&mut self.key);
+            return self.ser.serialize_blocks(Root::Field(key), value, None);
+        }
+
+        value.serialize(&mut *self.ser).map_err(|err| {
+            let key = std::mem::take(&mut self.key);
+            if self.fields {
+                err.prepend(PathSegment::Field(key))
+            } else {
+                err.prepend(PathSegment::Key(key))
+            }
+        })?;
+
+        if self.fields {
+            self.ser.separator()?;
+        }
         Ok(())
     }
 
-    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(&format!("float({v})"));
-        Ok(())
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.document || self.inline {
+            return Ok(());
+        }
+
+        if self.fields {
+            self.ser.depth -= 1;
+            self.ser.close_block()
+        } else {
+            self.ser.end_items(self.first)
+        }
     }
+}
+
+/// Serializer for the keys of a map, which
+/// must be text in Sirt.
+pub(crate) struct MapKeySerializer;
+
+fn key_must_be_text() -> SirtSerializeError {
+    ser::Error::custom("map keys must be text")
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Error = SirtSerializeError;
+    type Ok = String;
+    type SerializeStruct = Impossible<String, SirtSerializeError>;
+    type SerializeSeq = Impossible<String, SirtSerializeError>;
+    type SerializeMap = Impossible<String, SirtSerializeError>;
+    type SerializeStructVariant = Impossible<String, SirtSerializeError>;
+    type SerializeTuple = Impossible<String, SirtSerializeError>;
+    type SerializeTupleStruct = Impossible<String, SirtSerializeError>;
+    type SerializeTupleVariant = Impossible<String, SirtSerializeError>;
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(&format!("text(\"{v}\")"));
-        Ok(())
+        Ok(v.to_string())
     }
 
-    fn serialize_struct(
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, v: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        v.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_some<T>(self, _: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_unit_variant(
         self,
-        _name: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeStruct, Self::Error> {
-        Ok(SirtStructSerializer { ser: self })
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(key_must_be_text())
     }
 
     fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        let state = true;
-        self.output.push_str("list(");
-        self.pretty_newline();
-        if !state {
-            self.pretty_indent(2);
-        }
-        Ok(SirtListSerializer {
-            ser: self,
-            first: state,
-        })
+        Err(key_must_be_text())
     }
 
     fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        unimplemented!()
+        Err(key_must_be_text())
     }
 
     fn serialize_tuple_struct(
@@ -158,7 +628,7 @@ impl<'a> ser::Serializer for &'a mut SirtSerializer {
         _: &'static str,
         _: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        unimplemented!()
+        Err(key_must_be_text())
     }
 
     fn serialize_tuple_variant(
@@ -168,11 +638,19 @@ impl<'a> ser::Serializer for &'a mut SirtSerializer {
         _: &'static str,
         _: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        unimplemented!()
+        Err(key_must_be_text())
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        unimplemented!()
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(key_must_be_text())
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(key_must_be_text())
     }
 
     fn serialize_struct_variant(
@@ -182,126 +660,642 @@ impl<'a> ser::Serializer for &'a mut SirtSerializer {
         _: &'static str,
         _: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        unimplemented!()
+        Err(key_must_be_text())
     }
+}
 
-    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+pub struct SirtStructSerializer<'a, 'n, W> {
+    ser: &'a mut SirtSerializer<'n, W>,
+    /// Whether each field holds the blocks of a document.
+    document: bool,
+    /// Whether the struct is a [crate::Block], which is written
+    /// as a block named by its `name` field, with the entries of
+    /// its `fields` field.
+    block: bool,
+}
+
+impl<'a, 'n, W: fmt::Write> SerializeStruct for SirtStructSerializer<'a, 'n, W> {
+    type Ok = ();
+    type Error = SirtSerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.document {
+            return self
+                .ser
+                .serialize_blocks(Root::Field(key.to_string()), value, None);
+        }
+
+        if self.block {
+            if key == "name" {
+                let name = value.serialize(MapKeySerializer)?;
+                return self.ser.begin_struct(&name);
+            }
+
+            self.ser.inline_fields = true;
+            return value.serialize(&mut *self.ser);
+        }
+
+        self.ser.pretty_indent(self.ser.depth)?;
+        write!(self.ser, "{key}: ")?;
+        value
+            .serialize(&mut *self.ser)
+            .map_err(|err| err.prepend(PathSegment::Field(key.to_string())))?;
+        self.ser.separator()
     }
 
-    fn serialize_char(self, _: char) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.document {
+            return Ok(());
+        }
+
+        self.ser.depth -= 1;
+
+        if self.ser.nested() {
+            self.ser.pretty_indent(self.ser.depth)?;
+            self.ser.write_str("}")
+        } else {
+            self.ser.close_block()
+        }
     }
+}
 
-    fn serialize_f32(self, _: f32) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+impl<'a, 'n, W: fmt::Write> SerializeStructVariant for SirtStructSerializer<'a, 'n, W> {
+    type Ok = ();
+    type Error = SirtSerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeStruct::serialize_field(self, key, value)
     }
 
-    fn serialize_i128(self, _: i128) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeStruct::end(self)
     }
+}
 
-    fn serialize_i16(self, _: i16) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+impl<'a, 'n, W: fmt::Write> ser::Serializer for &'a mut SirtSerializer<'n, W> {
+    type Error = SirtSerializeError;
+    type Ok = ();
+    type SerializeStruct = SirtStructSerializer<'a, 'n, W>;
+    type SerializeSeq = SirtListSerializer<'a, 'n, W>;
+    type SerializeMap = SirtMapSerializer<'a, 'n, W>;
+    type SerializeStructVariant = SirtStructSerializer<'a, 'n, W>;
+    type SerializeTuple = SirtListSerializer<'a, 'n, W>;
+    type SerializeTupleStruct = SirtListSerializer<'a, 'n, W>;
+    type SerializeTupleVariant = SirtListSerializer<'a, 'n, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.push_value(format_args!("bool({v})"))
     }
 
-    fn serialize_i32(self, _: i32) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
     }
 
-    fn serialize_i8(self, _: i8) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.push_value(format_args!("int({v})"))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(to_int(v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(to_int(v)?)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(to_int(v)?)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        // the shortest representation of an f32 also reads
+        // back as the same f32 after parsing it as an f64
+        if v.is_finite() {
+            self.push_value(format_args!("float({v:?})"))
+        } else {
+            self.serialize_f64(f64::from(v))
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        // `Debug` writes the shortest representation that round-trips,
+        // always with either a fraction or an exponent
+        if v.is_nan() {
+            self.push_value(format_args!("float(nan)"))
+        } else if v.is_infinite() {
+            let sign = if v > 0.0 { "" } else { "-" };
+            self.push_value(format_args!("float({sign}inf)"))
+        } else {
+            self.push_value(format_args!("float({v:?})"))
+        }
     }
 
-    fn serialize_newtype_struct<T>(self, _: &'static str, _: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.push_value(format_args!("text(\""))?;
+        self.write_escaped(v)?;
+        self.write_str("\")")
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        let mut seq = self.serialize_seq(Some(v.len()))?;
+        for byte in v {
+            SerializeSeq::serialize_element(&mut seq, byte)?;
+        }
+        SerializeSeq::end(seq)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        // a field of a document without a block
+        if self.in_document() {
+            return Ok(());
+        }
+
+        self.push_value(format_args!("none"))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T>(
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_newtype_struct<T>(
         self,
         _: &'static str,
-        _: u32,
-        _: &'static str,
-        _: &T,
+        value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        value.serialize(self)
     }
 
-    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        // the block is opened once its name is known
+        if name == BLOCK_TOKEN {
+            return Ok(SirtStructSerializer {
+                ser: self,
+                document: false,
+                block: true,
+            });
+        }
+
+        if self.depth == 0
+            && let Root::Document = self.root
+        {
+            return Ok(SirtStructSerializer {
+                ser: self,
+                document: true,
+                block: false,
+            });
+        }
+        self.begin_struct(name)?;
+
+        Ok(SirtStructSerializer {
+            ser: self,
+            document: false,
+            block: false,
+        })
     }
 
-    fn serialize_some<T>(self, _: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        if self.in_document() {
+            return Ok(SirtListSerializer {
+                ser: self,
+                index: 0,
+                blocks: true,
+            });
+        }
+
+        self.push_value(format_args!("list("))?;
+        self.depth += 1;
+
+        Ok(SirtListSerializer {
+            ser: self,
+            index: 0,
+            blocks: false,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        if std::mem::take(&mut self.inline_fields) {
+            return Ok(SirtMapSerializer {
+                ser: self,
+                first: true,
+                fields: true,
+                document: false,
+                inline: true,
+                key: String::new(),
+            });
+        }
+
+        if self.depth == 0
+            && let Root::Document = self.root
+        {
+            return Ok(SirtMapSerializer {
+                ser: self,
+                first: true,
+                fields: false,
+                document: true,
+                inline: false,
+                key: String::new(),
+            });
+        }
+
+        // a map in place of the outermost block provides its fields
+        let fields = !self.nested();
+        if fields {
+            self.open_block(None)?;
+            self.separator()?;
+        } else {
+            self.write_str("map(")?;
+        }
+        self.depth += 1;
+
+        Ok(SirtMapSerializer {
+            ser: self,
+            first: true,
+            fields,
+            document: false,
+            inline: false,
+            key: String::new(),
+        })
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        // a block inside of a value
+        if variant == BLOCK_TOKEN {
+            return value.serialize(self);
+        }
+
+        self.begin_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.end_items(false)
     }
 
-    fn serialize_u128(self, _: u128) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.begin_variant(variant)?;
+        self.serialize_seq(Some(len))
     }
 
-    fn serialize_u16(self, _: u16) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        // a struct variant is written as a block named after the variant
+        self.check_nested()?;
+        self.serialize_struct(variant, len)
     }
+}
 
-    fn serialize_u32(self, _: u32) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+/// Converts an integer into [crate::types::Int],
+/// failing if it does not fit.
+pub(crate) fn to_int<T>(v: T) -> Result<Int, SirtSerializeError>
+where
+    T: Copy + std::fmt::Display + TryInto<Int>,
+{
+    v.try_into().map_err(|_| {
+        ser::Error::custom(format!(
+            "integer {v} is out of range for int, which must be between {} and {}",
+            Int::MIN,
+            Int::MAX
+        ))
+    })
+}
+
+/// Whether the string is a valid identifier,
+/// as used for the names of blocks and fields.
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Serializes `value` into `output` from the given root,
+/// returning the error that made a write to `output` fail
+/// instead of the one it caused.
+fn serialize_into<T, W>(
+    output: &mut W,
+    root: Root<'_>,
+    prettify: bool,
+    value: &T,
+) -> Result<(), SirtSerializeError>
+where
+    T: ?Sized + Serialize,
+    W: fmt::Write,
+{
+    let name = match root {
+        Root::Block(name) => Some(name),
+        _ => None,
+    };
+
+    let mut s = SirtSerializer::new(output, root, prettify);
+    value.serialize(&mut s).map_err(|err| match name {
+        Some(name) => err.prepend(PathSegment::Block(name.to_string())),
+        None => err,
+    })
+}
+
+/// Writes `value` from the given root for a `Display` impl,
+/// prettified with the alternate flag (`{:#}`).
+///
+/// Names are written as they are, so that only a failed
+/// write is an error.
+fn display<T>(f: &mut fmt::Formatter, root: Root<'_>, value: &T) -> fmt::Result
+where
+    T: ?Sized + Serialize,
+{
+    let prettify = f.alternate();
+    let mut s = SirtSerializer::new(f, root, prettify);
+    s.check_names = false;
+    value.serialize(&mut s).map_err(|_| fmt::Error)
+}
+
+/// Writes the value in Sirt syntax, as it would appear in a
+/// field, such as `list(int(1), int(2))`, prettified with `{:#}`.
+///
+/// A nested block whose name or fields are not identifiers is
+/// written as it is, which Sirt can't read back.
+/// [crate::Value::to_sirt_string] checks the names instead.
+impl fmt::Display for crate::Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        display(f, Root::Value, self)
     }
+}
 
-    fn serialize_u64(self, _: u64) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+/// Writes the block in Sirt syntax, same as [to_string],
+/// or [to_pretty_string] with `{:#}`.
+///
+/// A name or field that is not an identifier is written as
+/// it is, which Sirt can't read back.
+/// [crate::Block::to_sirt_string] checks the names instead.
+impl fmt::Display for crate::Block<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        display(f, Root::Struct, self)
     }
+}
 
-    fn serialize_u8(self, _: u8) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+/// Writes every block in Sirt syntax, one per line, same as
+/// [to_string_document], or [to_pretty_string_document] with `{:#}`.
+///
+/// A name or field that is not an identifier is written as
+/// it is, which Sirt can't read back.
+/// [crate::Document::to_sirt_string] checks the names instead.
+impl fmt::Display for crate::Document<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        display(f, Root::Document, self)
     }
+}
 
-    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+impl crate::Value<'_> {
+    /// Same as the `Display` output, but fails if a nested block
+    /// has a name or field that is not an identifier.
+    pub fn to_sirt_string(&self) -> Result<String, Error> {
+        serialize_string(Root::Value, false, self)
     }
 
-    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    /// Same as [crate::Value::to_sirt_string], but prettified.
+    pub fn to_pretty_sirt_string(&self) -> Result<String, Error> {
+        serialize_string(Root::Value, true, self)
     }
+}
 
-    fn serialize_unit_variant(
-        self,
-        _: &'static str,
-        _: u32,
-        _: &'static str,
-    ) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+impl crate::Block<'_> {
+    /// Same as the `Display` output, but fails if the block
+    /// has a name or field that is not an identifier.
+    pub fn to_sirt_string(&self) -> Result<String, Error> {
+        serialize_string(Root::Struct, false, self)
+    }
+
+    /// Same as [crate::Block::to_sirt_string], but prettified.
+    pub fn to_pretty_sirt_string(&self) -> Result<String, Error> {
+        serialize_string(Root::Struct, true, self)
     }
 }
 
+impl crate::Document<'_> {
+    /// Same as the `Display` output, but fails if a block
+    /// has a name or field that is not an identifier.
+    pub fn to_sirt_string(&self) -> Result<String, Error> {
+        serialize_string(Root::Document, false, self)
+    }
+
+    /// Same as [crate::Document::to_sirt_string], but prettified.
+    pub fn to_pretty_sirt_string(&self) -> Result<String, Error> {
+        serialize_string(Root::Document, true, self)
+    }
+}
+
+/// Serializes into an [io::Write], returning [Error::Io]
+/// if writing fails.
+fn serialize_io<T, W>(writer: W, root: Root<'_>, prettify: bool, value: &T) -> Result<(), Error>
+where
+    T: ?Sized + Serialize,
+    W: io::Write,
+{
+    let mut output = IoWriter {
+        writer,
+        error: None,
+    };
+
+    serialize_into(&mut output, root, prettify, value).map_err(|err| match output.error {
+        Some(err) => Error::Io(err),
+        None => err.into(),
+    })
+}
+
+fn serialize_string<T>(root: Root<'_>, prettify: bool, value: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut output = String::new();
+    serialize_into(&mut output, root, prettify, value)?;
+    Ok(output)
+}
+
+/// Serializes an object as a block into an [io::Write],
+/// writing the output as it is produced.
+///
+/// Each piece of the output is written separately, so
+/// the writer should be buffered, such as a
+/// [std::io::BufWriter] around a file.
+///
+/// # Note
+/// This does not write a prettified block, use
+/// [to_writer_pretty] for this.
+pub fn to_writer<W, T>(writer: W, name: &str, value: &T) -> Result<(), Error>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    serialize_io(writer, Root::Block(name), false, value)
+}
+
+/// Same as [to_writer], but writes a prettified version.
+pub fn to_writer_pretty<W, T>(writer: W, name: &str, value: &T) -> Result<(), Error>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    serialize_io(writer, Root::Block(name), true, value)
+}
+
+/// Same as [to_writer], but writes into a [fmt::Write],
+/// such as a `String` or a [fmt::Formatter].
+pub fn to_fmt_writer<W, T>(mut writer: W, name: &str, value: &T) -> Result<(), Error>
+where
+    W: fmt::Write,
+    T: ?Sized + Serialize,
+{
+    Ok(serialize_into(
+        &mut writer,
+        Root::Block(name),
+        false,
+        value,
+    )?)
+}
+
+/// Same as [to_fmt_writer], but writes a prettified version.
+pub fn to_fmt_writer_pretty<W, T>(mut writer: W, name: &str, value: &T) -> Result<(), Error>
+where
+    W: fmt::Write,
+    T: ?Sized + Serialize,
+{
+    Ok(serialize_into(&mut writer, Root::Block(name), true, value)?)
+}
+
 /// Serializes an object into string representation.
 ///
 /// # Note
 /// This does not return a prettified string, use
 /// [to_pretty_string] for this.
-pub fn to_string<T>(name: &str, value: &T) -> Result<String, SirtSerializeError>
+pub fn to_string<T>(name: &str, value: &T) -> Result<String, Error>
 where
     T: Serialize,
 {
-    let mut s = SirtSerializer::new(name, false);
-    value.serialize(&mut s)?;
-    Ok(s.complete())
+    serialize_string(Root::Block(name), false, value)
 }
 
 /// Same as [to_string], but returns a prettified version.
-pub fn to_pretty_string<T>(name: &str, value: &T) -> Result<String, SirtSerializeError>
+pub fn to_pretty_string<T>(name: &str, value: &T) -> Result<String, Error>
 where
     T: Serialize,
 {
-    let mut s = SirtSerializer::new(name, true);
-    value.serialize(&mut s)?;
-    Ok(s.complete())
+    serialize_string(Root::Block(name), true, value)
+}
+
+/// Serializes a whole document, mirroring [crate::from_str_document].
+///
+/// The value can be a struct or map whose fields hold blocks,
+/// where a `Vec` field is written as a block for each element
+/// and a `None` field is left out, or a list of structs that
+/// are each written as a block.
+///
+/// A block is named after its struct. In a field, this is only
+/// the case if the struct name matches the field name when case
+/// and underscores are ignored, otherwise the block is named
+/// after the field, so `dev_dependency` is written as a block
+/// named `DevDependency`.
+///
+/// # Note
+/// This does not return a prettified string, use
+/// [to_pretty_string_document] for this.
+pub fn to_string_document<T>(value: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    serialize_string(Root::Document, false, value)
+}
+
+/// Same as [to_string_document], but returns a prettified
+/// version, with an empty line between blocks.
+pub fn to_pretty_string_document<T>(value: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    serialize_string(Root::Document, true, value)
 }
@@ -0,0 +1,125 @@
+//! Locations of blocks, fields and values in the input.
+//!
+//! Spans are not stored inside of a [crate::Block] itself,
+//! they are returned as a separate [SourceMap] by
+//! [crate::parse_input_spanned].
+
+use indexmap::IndexMap;
+use std::ops::Range;
+
+/// A region of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// Byte offset of the start of the region.
+    pub start: usize,
+    /// Byte offset of the end of the region (exclusive).
+    pub end: usize,
+    /// Line of the start of the region, starting at 1.
+    pub line: usize,
+    /// Column of the start of the region in characters, starting at 1.
+    pub column: usize,
+}
+
+impl Span {
+    /// Returns the byte range of the region.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// Locations of a single field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSpans {
+    /// The name of the field.
+    pub key: Span,
+    /// The value of the field, including its type.
+    pub value: Span,
+    /// The locations inside of the value, if it is a nested block.
+    pub block: Option<Box<BlockSpans>>,
+}
+
+/// Locations of a block and its fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSpans {
+    /// The whole block, from its name to the closing brace.
+    pub span: Span,
+    /// The name of the block.
+    pub name: Span,
+    pub(crate) fields: IndexMap<String, FieldSpans>,
+}
+
+impl BlockSpans {
+    /// Returns the locations of the field with the given name.
+    pub fn field(&self, key: &str) -> Option<&FieldSpans> {
+        self.fields.get(key)
+    }
+
+    /// Returns a reference to the locations of every field,
+    /// in the order they were written.
+    pub fn get_fields(&self) -> &IndexMap<String, FieldSpans> {
+        &self.fields
+    }
+}
+
+/// The locations of every parsed block, in the same
+/// order as the blocks themselves.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceMap {
+    pub(crate) blocks: Vec<BlockSpans>,
+}
+
+impl SourceMap {
+    /// Returns the locations of the block at the given index.
+    pub fn block(&self, index: usize) -> Option<&BlockSpans> {
+        self.blocks.get(index)
+    }
+
+    /// Returns the locations of a field of the block at
+    /// the given index.
+    pub fn field(&self, index: usize, key: &str) -> Option<&FieldSpans> {
+        self.block(index)?.field(key)
+    }
+
+    /// Returns an iterator over the locations of every block.
+    pub fn iter(&self) -> std::slice::Iter<'_, BlockSpans> {
+        self.blocks.iter()
+    }
+}
+
+impl IntoIterator for SourceMap {
+    type Item = BlockSpans;
+    type IntoIter = std::vec::IntoIter<BlockSpans>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.blocks.into_iter()
+    }
+}
+
+/// Byte offsets of the start of every line, used to
+/// turn byte offsets into lines and columns.
+pub(crate) struct LineIndex<'i> {
+    input: &'i str,
+    starts: Vec<usize>,
+}
+
+impl<'i> LineIndex<'i> {
+    pub(crate) fn new(input: &'i str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self { input, starts }
+    }
+
+    pub(crate) fn span(&self, range: Range<usize>) -> Span {
+        let line = self.starts.partition_point(|start| *start <= range.start);
+        let line_start = self.starts[line - 1];
+
+        Span {
+            start: range.start,
+            end: range.end,
+            line,
+            column: self.input[line_start..range.start].chars().count() + 1,
+        }
+    }
+}
@@ -0,0 +1,710 @@
+//! Conversion between Rust types and [Value] trees, and the
+//! serde impls of [Value], [Block] and [Document].
+
+use crate::de::ValueDeserializer;
+use crate::error::{Error, PathSegment, SirtSerializeError};
+use crate::se::{MapKeySerializer, to_int};
+use crate::{Block, Document, Value};
+
+use indexmap::IndexMap;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{
+    self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer,
+};
+use std::borrow::Cow;
+use std::fmt;
+
+/// The name a [Block] is serialized under, as a struct with
+/// a `name` and a `fields` field.
+///
+/// Other formats see an ordinary struct, while the
+/// serializers of this crate write an actual block.
+/// A block inside of a [Value] is also wrapped in a variant
+/// of this name, so that other formats write it as a map
+/// with a single [BLOCK_TOKEN] entry, which tells it apart
+/// from a map when it is read back.
+pub(crate) const BLOCK_TOKEN: &str = "$libsirt::Block";
+
+/// The name of the newtype struct a [Value] is deserialized
+/// as, so that the deserializers of this crate can give back
+/// a block as a map with a single [BLOCK_TOKEN] entry, while
+/// other formats deserialize the value as usual.
+pub(crate) const VALUE_TOKEN: &str = "$libsirt::Value";
+
+/// The key of the single entry that the deserializers of this
+/// crate give back a map under, for a [Value], so that a map
+/// is never mistaken for a block.
+pub(crate) const MAP_TOKEN: &str = "$libsirt::Map";
+
+impl Serialize for Value<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Text(text) => serializer.serialize_str(text),
+            Value::Int(n) => serializer.serialize_i64(*n),
+            Value::Float(n) => serializer.serialize_f64(*n),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::List(list) => list.serialize(serializer),
+            Value::Map(map) => map.serialize(serializer),
+            // tagged, so that other formats don't read it back as a map
+            Value::Block(block) => {
+                serializer.serialize_newtype_variant(VALUE_TOKEN, 0, BLOCK_TOKEN, block)
+            }
+            Value::None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl Serialize for Block<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut block = serializer.serialize_struct(BLOCK_TOKEN, 2)?;
+        block.serialize_field("name", &self.name)?;
+        block.serialize_field("fields", &self.fields)?;
+        block.end()
+    }
+}
+
+/// A document is serialized as a list of its blocks.
+impl Serialize for Document<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.get_blocks().serialize(serializer)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value<'static>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Sirt value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Self::Value, E> {
+        to_int(v).map(Value::Int).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        to_int(v).map(Value::Int).map_err(E::custom)
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        to_int(v).map(Value::Int).map_err(E::custom)
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Value::Text(Cow::Owned(v.to_string())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Value::Text(Cow::Owned(v)))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Value::List(
+            v.iter().map(|b| Value::Int(i64::from(*b))).collect(),
+        ))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = IndexMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(key) = map.next_key::<String>()? {
+            if entries.is_empty() && key == BLOCK_TOKEN {
+                return map.next_value().map(Value::Block);
+            }
+            if entries.is_empty() && key == MAP_TOKEN {
+                let entries: IndexMap<String, Value> = map.next_value()?;
+                return Ok(Value::Map(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (Cow::Owned(key), value))
+                        .collect(),
+                ));
+            }
+
+            entries.insert(Cow::Owned(key), map.next_value()?);
+        }
+
+        Ok(Value::Map(entries))
+    }
+}
+
+/// Text is always copied, so that a value can be deserialized
+/// from any format. A block is read back from a map with a
+/// single [BLOCK_TOKEN] entry, as other formats write it.
+impl<'de> Deserialize<'de> for Value<'_> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(VALUE_TOKEN, ValueVisitor)
+    }
+}
+
+struct BlockVisitor;
+
+impl<'de> Visitor<'de> for BlockVisitor {
+    type Value = Block<'static>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Sirt block")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let name: String = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let fields: IndexMap<String, Value> = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok(block(name, fields))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut name: Option<String> = None;
+        let mut fields: Option<IndexMap<String, Value>> = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" if name.is_some() => return Err(de::Error::duplicate_field("name")),
+                "name" => name = Some(map.next_value()?),
+                "fields" if fields.is_some() => return Err(de::Error::duplicate_field("fields")),
+                "fields" => fields = Some(map.next_value()?),
+                other => return Err(de::Error::unknown_field(other, &["name", "fields"])),
+            }
+        }
+
+        let name = name.ok_or_else(|| de::Error::missing_field("name"))?;
+        let fields = fields.ok_or_else(|| de::Error::missing_field("fields"))?;
+        Ok(block(name, fields))
+    }
+}
+
+fn block(name: String, fields: IndexMap<String, Value<'static>>) -> Block<'static> {
+    Block::with_fields(
+        name,
+        fields
+            .into_iter()
+            .map(|(key, value)| (Cow::Owned(key), value))
+            .collect(),
+    )
+}
+
+/// A block is read back from a struct or map with a `name`
+/// and a `fields` entry, as it is serialized by other formats,
+/// or from a block when using the deserializers of this crate.
+impl<'de> Deserialize<'de> for Block<'_> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(BLOCK_TOKEN, &["name", "fields"], BlockVisitor)
+    }
+}
+
+/// A document is read back from a list of blocks, or from
+/// every block of the input with [crate::from_str_document].
+impl<'de> Deserialize<'de> for Document<'_> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<Block>::deserialize(deserializer).map(Document::from)
+    }
+}
+
+/// Serializes into a [Value], using the same representation
+/// as [crate::to_string], where a struct becomes a block.
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = ListSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(to_int(v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(to_int(v)?)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(to_int(v)?)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        // the same float that is read back from the shortest
+        // representation written by `to_string`, such as 0.1
+        // for `0.1f32`, where `f64::from` would keep the error
+        // of the f32 and give 0.10000000149011612, so that the
+        // value would be written differently than the f32 itself
+        match v.to_string().parse() {
+            Ok(n) if v.is_finite() => Ok(Value::Float(n)),
+            _ => self.serialize_f64(f64::from(v)),
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Text(Cow::Owned(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Text(Cow::Owned(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::List(
+            v.iter().map(|b| Value::Int(i64::from(*b))).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        // a block inside of a value
+        if variant == BLOCK_TOKEN {
+            return value.serialize(ValueSerializer);
+        }
+
+        let value = value.serialize(ValueSerializer)?;
+        Ok(Value::Map(IndexMap::from([(
+            Cow::Borrowed(variant),
+            value,
+        )])))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(ListSerializer {
+            list: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(ListSerializer {
+            list: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(MapSerializer {
+            map: IndexMap::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(StructSerializer {
+            name,
+            fields: IndexMap::with_capacity(len),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        // a struct variant is a block named after the variant
+        self.serialize_struct(variant, len)
+    }
+}
+
+struct ListSerializer {
+    list: Vec<Value<'static>>,
+    /// The variant that holds the list, for a tuple variant.
+    variant: Option<&'static str>,
+}
+
+impl SerializeSeq for ListSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.list.len();
+        let value = value
+            .serialize(ValueSerializer)
+            .map_err(|err| err.prepend(PathSegment::Index(index)))?;
+        self.list.push(value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        let list = Value::List(self.list);
+
+        Ok(match self.variant {
+            Some(variant) => Value::Map(IndexMap::from([(Cow::Borrowed(variant), list)])),
+            None => list,
+        })
+    }
+}
+
+impl SerializeTuple for ListSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for ListSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleVariant for ListSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct MapSerializer {
+    map: IndexMap<Cow<'static, str>, Value<'static>>,
+    key: Option<String>,
+}
+
+impl SerializeMap for MapSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(key.serialize(MapKeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("serialize_value called before serialize_key"))?;
+
+        match value.serialize(ValueSerializer) {
+            Ok(value) => {
+                self.map.insert(Cow::Owned(key), value);
+                Ok(())
+            }
+            Err(err) => Err(err.prepend(PathSegment::Key(key))),
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Map(self.map))
+    }
+}
+
+struct StructSerializer {
+    name: &'static str,
+    fields: IndexMap<Cow<'static, str>, Value<'static>>,
+}
+
+impl SerializeStruct for StructSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let value = value
+            .serialize(ValueSerializer)
+            .map_err(|err| err.prepend(PathSegment::Field(key.to_string())))?;
+        self.fields.insert(Cow::Borrowed(key), value);
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        if self.name != BLOCK_TOKEN {
+            return Ok(Value::Block(Block::with_fields(self.name, self.fields)));
+        }
+
+        // a block that was serialized as its name and fields
+        match (
+            self.fields.shift_remove("name"),
+            self.fields.shift_remove("fields"),
+        ) {
+            (Some(Value::Text(name)), Some(Value::Map(fields))) => {
+                Ok(Value::Block(Block::with_fields(name, fields)))
+            }
+            _ => Err(ser::Error::custom("invalid block")),
+        }
+    }
+}
+
+impl SerializeStructVariant for StructSerializer {
+    type Ok = Value<'static>;
+    type Error = SirtSerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeStruct::end(self)
+    }
+}
+
+/// Converts a value into a [Value] tree, such as to inspect
+/// or edit it before writing it.
+///
+/// A struct becomes a [Value::Block] named after the struct,
+/// and enums are represented the same way as by
+/// [crate::to_string].
+///
+/// ```
+/// use libsirt::{Value, to_value};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Server {
+///     port: u16,
+/// }
+///
+/// let value = to_value(&Server { port: 8080 }).unwrap();
+/// let Value::Block(block) = value else { panic!() };
+///
+/// assert_eq!(block.get_name(), "Server");
+/// assert_eq!(block.get_int("port").unwrap(), 8080);
+/// ```
+///
+/// # Error
+/// If a value is not supported, such as an integer that
+/// does not fit in [crate::types::Int], it returns an
+/// Err([Error::Serialize]).
+pub fn to_value<T>(value: &T) -> Result<Value<'static>, Error>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(value.serialize(ValueSerializer)?)
+}
+
+/// Deserializes an object of type `T` from a [Value],
+/// the same way as from parsed input.
+///
+/// A block or a map can be deserialized into a struct.
+pub fn from_value<'de, T>(value: Value<'de>) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    Ok(T::deserialize(ValueDeserializer::new(&value))?)
+}
@@ -1,5 +1,15 @@
-use libsirt::{from_str, from_str_named, from_str_named_iter};
-use serde::Deserialize;
+use libsirt::error::{ParseError, ParseErrorKind, PathSegment, ValueErrorKind};
+use libsirt::options::ParseOptions;
+use libsirt::{
+    Block, Document, Error, Value, from_reader_iter, from_str, from_str_document, from_str_named,
+    from_str_named_iter, from_str_named_iter_with, from_str_with, from_value, parse_input,
+    to_fmt_writer, to_fmt_writer_pretty, to_pretty_string, to_pretty_string_document, to_string,
+    to_string_document, to_value, to_writer, to_writer_pretty, types,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as _;
 
 #[test]
 fn test_from_str() {
@@ -83,3 +93,1247 @@ fn test_from_str_named_iter() {
     assert_eq!(iter.next(), Some(logging));
     assert_eq!(iter.next(), Some(compact));
 }
+
+#[test]
+fn test_from_reader_iter() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        name: String,
+        value: bool,
+    }
+
+    let i = r#"
+    Person {}
+    Config { name: text("enableBlur") value: bool(true) }
+    Config { name: text("enableLogging") value: bool(false) }
+    Config { name: text("compactMode") value: int(1) }
+    Config { name: text("afterError") value: bool(true) }
+    "#;
+
+    let mut configs = from_reader_iter::<Config, _>(i.as_bytes(), "Config");
+    let names: Vec<String> = configs
+        .by_ref()
+        .take(2)
+        .map(|config| config.unwrap().name)
+        .collect();
+    assert_eq!(names, ["enableBlur", "enableLogging"]);
+
+    let err = configs.next().unwrap().unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Config.value: expected bool, found int"
+    );
+    assert_eq!(configs.next().unwrap().unwrap().name, "afterError");
+    assert!(configs.next().is_none());
+
+    let i = "Config { name: text(\"x\") value: bool(true) }\nConfig { name:";
+    let configs: Vec<_> = from_reader_iter::<Config, _>(i.as_bytes(), "Config").collect();
+    assert!(configs[0].is_ok());
+    assert!(matches!(&configs[1], Err(Error::Parse(err)) if err.line() == 2));
+}
+
+#[test]
+fn test_from_str_document() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        package: Package,
+        dependency: Vec<Dependency>,
+        dev_dependency: Vec<Dependency>,
+        workspace: Option<Package>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Package {
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Dependency {
+        name: String,
+        version: String,
+    }
+
+    let i = r#"
+    Dependency { name: text("serde") version: text("1.0") }
+    Package { name: text("sirt") }
+    Unknown {}
+    Dependency { name: text("indexmap") version: text("2.13") }
+    DevDependency { name: text("criterion") version: text("0.5") }
+    "#;
+
+    let config: Config = from_str_document(i).unwrap();
+    assert_eq!(config.package.name, "sirt");
+    assert_eq!(config.dependency.len(), 2);
+    assert_eq!(config.dependency[1].version, "2.13");
+    assert_eq!(config.dev_dependency[0].name, "criterion");
+    assert_eq!(config.workspace, None);
+
+    let i = r#"
+    Package { name: text("sirt") }
+    Dependency { name: text("serde") version: int(1) }
+    "#;
+    let err = from_str_document::<Config>(i).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Dependency[0].version: expected text, found int at line 3, column 47"
+    );
+
+    let i = r#"
+    Package { name: text("a") }
+    Package { name: text("b") }
+    "#;
+    let err = from_str_document::<Config>(i).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Package: expected one block, found 2 at line 3, column 5"
+    );
+
+    let i = r#"Dependency { name: text("serde") version: text("1.0") }"#;
+    let err = from_str_document::<Config>(i).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "package: missing block `Package`"
+    );
+
+    // a list without any blocks is empty
+    let config: Config = from_str_document(r#"Package { name: text("sirt") }"#).unwrap();
+    assert_eq!(config.package.name, "sirt");
+    assert!(config.dependency.is_empty());
+    assert!(config.dev_dependency.is_empty());
+
+    // a field with a default is left out when no block matches it
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Defaulted {
+        #[serde(default)]
+        package: Package,
+        dependency: Vec<Dependency>,
+    }
+
+    impl Default for Package {
+        fn default() -> Self {
+            Package {
+                name: "default".to_string(),
+            }
+        }
+    }
+
+    let i = r#"Dependency { name: text("serde") version: text("1.0") }"#;
+    let config: Defaulted = from_str_document(i).unwrap();
+    assert_eq!(config.package.name, "default");
+    assert_eq!(config.dependency[0].name, "serde");
+
+    let config: Defaulted = from_str_document("").unwrap();
+    assert_eq!(config.package.name, "default");
+    assert!(config.dependency.is_empty());
+
+    // without a struct, blocks are grouped by name
+    let names: BTreeMap<String, Vec<BTreeMap<String, String>>> =
+        from_str_document(r#"A { x: text("1") } B {} A { x: text("2") }"#).unwrap();
+    assert_eq!(names["A"].len(), 2);
+    assert_eq!(names["B"], [BTreeMap::new()]);
+}
+
+#[test]
+fn test_to_string_document() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        package: Package,
+        dependency: Vec<Dependency>,
+        dev_dependency: Vec<Dependency>,
+        workspace: Option<Package>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Package {
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Dependency {
+        name: String,
+        version: i64,
+    }
+
+    let dependency = |name: &str, version| Dependency {
+        name: name.to_string(),
+        version,
+    };
+    let config = Config {
+        package: Package {
+            name: "sirt".to_string(),
+        },
+        dependency: vec![dependency("serde", 1), dependency("indexmap", 2)],
+        dev_dependency: vec![dependency("criterion", 0)],
+        workspace: None,
+    };
+
+    let s = to_string_document(&config).unwrap();
+    assert_eq!(
+        s,
+        r#"Package { name: text("sirt") }
+Dependency { name: text("serde") version: int(1) }
+Dependency { name: text("indexmap") version: int(2) }
+DevDependency { name: text("criterion") version: int(0) }"#
+    );
+    assert_eq!(from_str_document::<Config>(&s).unwrap(), config);
+
+    let s = to_pretty_string_document(&config).unwrap();
+    assert!(s.starts_with("Package {\n\tname: text(\"sirt\")\n}\n\nDependency {\n"));
+    assert_eq!(from_str_document::<Config>(&s).unwrap(), config);
+
+    // an empty list and a none field are left out, and read back
+    let empty = Config {
+        package: Package {
+            name: "sirt".to_string(),
+        },
+        dependency: vec![],
+        dev_dependency: vec![],
+        workspace: None,
+    };
+    let s = to_string_document(&empty).unwrap();
+    assert_eq!(s, r#"Package { name: text("sirt") }"#);
+    assert_eq!(from_str_document::<Config>(&s).unwrap(), empty);
+
+    // a list of structs is written as blocks named after them
+    let s = to_string_document(&(
+        Package {
+            name: "sirt".to_string(),
+        },
+        dependency("serde", 1),
+    ))
+    .unwrap();
+    assert_eq!(
+        s,
+        "Package { name: text(\"sirt\") }\nDependency { name: text(\"serde\") version: int(1) }"
+    );
+
+    let mut blocks = BTreeMap::new();
+    blocks.insert("Versions", vec![BTreeMap::from([("serde", 1)])]);
+    assert_eq!(
+        to_string_document(&blocks).unwrap(),
+        "Versions { serde: int(1) }"
+    );
+
+    let err = to_string_document(&vec![BTreeMap::from([("serde", u64::MAX)])]).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "[0]: a map can only be serialized as a block if it has a name"
+    );
+
+    let err = to_string_document(&BTreeMap::from([(
+        "Sizes",
+        vec![BTreeMap::from([("a", u64::MAX)])],
+    )]))
+    .unwrap_err();
+    assert!(
+        err.source()
+            .unwrap()
+            .to_string()
+            .starts_with("Sizes[0].a: integer"),
+        "{err}"
+    );
+
+    let err = to_string_document(&1).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "only a struct or a map can be serialized as a block"
+    );
+}
+
+#[test]
+fn test_text_round_trip() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        body: String,
+    }
+
+    for body in [
+        "plain",
+        "with \"quotes\"",
+        "back\\slash",
+        "lines\nand\ttabs\r",
+        "bell \u{7} and nul \0",
+        "\u{1F980} crab",
+    ] {
+        let message = Message {
+            body: body.to_string(),
+        };
+
+        let s = to_string("Message", &message).unwrap();
+        assert_eq!(from_str::<Message>(&s).unwrap(), message);
+
+        let s = to_pretty_string("Message", &message).unwrap();
+        assert_eq!(from_str::<Message>(&s).unwrap(), message);
+    }
+}
+
+#[test]
+fn test_nested_blocks() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Size {
+        width: i64,
+        height: i64,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Window {
+        title: String,
+        size: Size,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct App {
+        window: Window,
+        popups: Vec<Window>,
+    }
+
+    let i = r#"
+    App {
+        window: Window {
+            title: text("main")
+            size: Size { width: int(800) height: int(600) }
+        }
+        popups: list()
+    }
+    "#;
+
+    let mut app: App = from_str(i).unwrap();
+    assert_eq!(app.window.size.width, 800);
+
+    app.popups.push(Window {
+        title: "about".to_string(),
+        size: Size {
+            width: 200,
+            height: 100,
+        },
+    });
+
+    let s = to_string("App", &app).unwrap();
+    assert_eq!(from_str::<App>(&s).unwrap(), app);
+
+    let s = to_pretty_string("App", &app).unwrap();
+    assert_eq!(from_str::<App>(&s).unwrap(), app);
+}
+
+#[test]
+fn test_maps() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Env {
+        vars: HashMap<String, i64>,
+        groups: BTreeMap<String, Vec<String>>,
+    }
+
+    let i =
+        r#"Env { vars: map("a": int(1), "b": int(2)) groups: map("admins": list(text("root"))) }"#;
+    let env: Env = from_str(i).unwrap();
+
+    assert_eq!(
+        env.vars,
+        HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)])
+    );
+    assert_eq!(env.groups["admins"], vec!["root".to_string()]);
+
+    let s = to_string("Env", &env).unwrap();
+    assert_eq!(from_str::<Env>(&s).unwrap(), env);
+
+    let s = to_pretty_string("Env", &env).unwrap();
+    assert_eq!(from_str::<Env>(&s).unwrap(), env);
+}
+
+#[test]
+fn test_options() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Server {
+        host: Option<String>,
+        port: Option<i64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timeout: Option<f64>,
+        tags: Vec<Option<String>>,
+    }
+
+    let i = r#"Server { host: text("localhost") port: none tags: list(none, text("a")) }"#;
+    let server: Server = from_str(i).unwrap();
+
+    assert_eq!(
+        server,
+        Server {
+            host: Some("localhost".to_string()),
+            port: None,
+            timeout: None,
+            tags: vec![None, Some("a".to_string())],
+        }
+    );
+
+    let s = to_string("Server", &server).unwrap();
+    assert!(s.contains("port: none"));
+    assert!(!s.contains("timeout"));
+    assert_eq!(from_str::<Server>(&s).unwrap(), server);
+
+    // a nested block can still be named none
+    let block = Block::new("A")
+        .with_field("x", Block::new("none").with_field("y", Value::None))
+        .with_field("z", Value::None);
+    let s = to_string("A", &block).unwrap();
+    assert_eq!(s, "A { x: none { y: none } z: none }");
+    assert_eq!(parse_input(&s).unwrap(), [block]);
+}
+
+#[test]
+fn test_serde_data_model() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Meters(u32);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Marker;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pair(i8, String);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Everything {
+        port: u16,
+        small: i8,
+        medium: i32,
+        big: u64,
+        huge: i128,
+        ratio: f32,
+        initial: char,
+        distance: Meters,
+        marker: Marker,
+        unit: (),
+        point: (i64, f64),
+        pair: Pair,
+        bytes: Vec<u8>,
+    }
+
+    let everything = Everything {
+        port: 8080,
+        small: -8,
+        medium: i32::MIN,
+        big: i64::MAX as u64,
+        huge: -42,
+        ratio: 0.1,
+        initial: 'S',
+        distance: Meters(42),
+        marker: Marker,
+        unit: (),
+        point: (1, -2.5),
+        pair: Pair(3, "three".to_string()),
+        bytes: vec![0, 255],
+    };
+
+    let s = to_string("Everything", &everything).unwrap();
+    assert_eq!(from_str::<Everything>(&s).unwrap(), everything);
+
+    let s = to_pretty_string("Everything", &everything).unwrap();
+    assert_eq!(from_str::<Everything>(&s).unwrap(), everything);
+}
+
+#[test]
+fn test_serialize_errors() {
+    #[derive(Serialize)]
+    struct Unsigned {
+        big: u64,
+    }
+
+    #[derive(Serialize)]
+    struct Wide {
+        wide: u128,
+    }
+
+    assert!(to_string("Unsigned", &Unsigned { big: u64::MAX }).is_err());
+    assert!(to_string("Wide", &Wide { wide: u128::MAX }).is_err());
+    assert!(to_string("Number", &10).is_err());
+    assert!(to_string("List", &vec![1, 2]).is_err());
+
+    let map = BTreeMap::from([("valid_name", 1)]);
+    assert_eq!(
+        to_string("Map", &map).unwrap(),
+        "Map { valid_name: int(1) }"
+    );
+
+    let map = BTreeMap::from([("not a name", 1)]);
+    assert!(to_string("Map", &map).is_err());
+}
+
+#[test]
+fn test_block_mutation() {
+    let mut blocks =
+        parse_input(r#"Server { host: text("localhost") port: int(80) debug: bool(no) }"#).unwrap();
+    let block = &mut blocks[0];
+
+    assert_eq!(block.insert_field("port", 8080), Some(Value::Int(80)));
+    assert_eq!(block.insert_field("workers", 4), None);
+    assert_eq!(block.remove_field("debug"), Some(Value::Bool(false)));
+    assert_eq!(block.remove_field("debug"), None);
+
+    if let Some(Value::Text(host)) = block.get_mut("host") {
+        *host = "0.0.0.0".into();
+    }
+
+    assert!(block.rename_field("host", "address"));
+    assert!(!block.rename_field("host", "address"));
+    assert!(!block.rename_field("address", "port"));
+    assert!(block.rename_field("port", "port"));
+    assert!(!block.rename_field("host", "host"));
+    block.set_name("Listener");
+
+    let expected = Block::new("Listener")
+        .with_field("address", "0.0.0.0")
+        .with_field("port", 8080)
+        .with_field("workers", 4);
+    assert_eq!(*block, expected);
+
+    let keys: Vec<_> = block.get_fields().keys().collect();
+    assert_eq!(keys, ["address", "port", "workers"]);
+
+    let nested = Block::new("Config")
+        .with_field("listener", expected.clone())
+        .with_field(
+            "tags",
+            vec![Value::from("a"), Value::from(String::from("b"))],
+        );
+    assert_eq!(nested.get("listener"), Some(&Value::Block(expected)));
+
+    let document: Document = blocks.into_iter().collect();
+    assert_eq!(
+        document.get_named("Listener").unwrap().get("port"),
+        Some(&Value::Int(8080))
+    );
+}
+
+#[test]
+fn test_typed_accessors() {
+    let blocks = parse_input(
+        r#"Server {
+            host: text("localhost")
+            port: int(8080)
+            ratio: float(0.5)
+            debug: bool(yes)
+            ports: list(int(80), int(443))
+            limits: map("cpu": int(2))
+            tls: Tls { enabled: bool(no) }
+            backup: none
+        }"#,
+    )
+    .unwrap();
+    let server = &blocks[0];
+
+    assert_eq!(server.get_text("host").unwrap(), "localhost");
+    assert_eq!(server.get_int("port").unwrap(), 8080);
+    assert_eq!(server.get_float("ratio").unwrap(), 0.5);
+    assert!(server.get_bool("debug").unwrap());
+    assert_eq!(
+        server.get_list("ports").unwrap(),
+        [Value::Int(80), Value::Int(443)]
+    );
+    assert_eq!(server.get_map("limits").unwrap()["cpu"], Value::Int(2));
+    assert!(
+        !server
+            .get_block("tls")
+            .unwrap()
+            .get_bool("enabled")
+            .unwrap()
+    );
+    assert!(server.get("backup").unwrap().is_none());
+
+    let err = server.get_int("host").unwrap_err();
+    assert_eq!(
+        *err.kind(),
+        ValueErrorKind::TypeMismatch {
+            expected: "int",
+            found: "text"
+        }
+    );
+    assert_eq!(err.to_string(), "Server.host: expected int, found text");
+
+    let err = server.get_text("missing").unwrap_err();
+    assert_eq!(err.to_string(), "Server: missing field `missing`");
+    assert!(matches!(Error::from(err), Error::Value(_)));
+
+    let value = Value::Text("text".into());
+    assert_eq!(value.type_name(), "text");
+    assert_eq!(value.as_text(), Some("text"));
+    assert_eq!(value.as_int(), None);
+
+    let ports: types::ListInt = server.get("ports").unwrap().clone().try_into().unwrap();
+    assert_eq!(ports, [80, 443]);
+    let limits: types::Map<types::Int> = server.get("limits").unwrap().clone().try_into().unwrap();
+    assert_eq!(limits["cpu"], 2);
+    let host: types::Text = server.get("host").unwrap().clone().try_into().unwrap();
+    assert_eq!(host, "localhost");
+
+    let list = Value::List(vec![Value::Int(1), Value::Bool(true)]);
+    let err = types::ListInt::try_from(list).unwrap_err();
+    assert_eq!(err.to_string(), "[1]: expected int, found bool");
+    assert!(types::Float::try_from(Value::Int(1)).is_err());
+}
+
+#[test]
+fn test_value_serde() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Server {
+        host: String,
+        ports: Vec<u16>,
+        tls: Tls,
+        mode: Mode,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Tls {
+        enabled: bool,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Mode {
+        Fast,
+        Limit(u32),
+    }
+
+    let server = Server {
+        host: "localhost".to_string(),
+        ports: vec![80, 443],
+        tls: Tls { enabled: true },
+        mode: Mode::Limit(10),
+    };
+
+    let value = to_value(&server).unwrap();
+    let expected = Block::new("Server")
+        .with_field("host", "localhost")
+        .with_field("ports", vec![Value::Int(80), Value::Int(443)])
+        .with_field("tls", Block::new("Tls").with_field("enabled", true))
+        .with_field(
+            "mode",
+            Value::Map([("Limit".into(), Value::Int(10))].into_iter().collect()),
+        );
+    assert_eq!(value, Value::Block(expected.clone()));
+    assert_eq!(from_value::<Server>(value).unwrap(), server);
+    assert_eq!(to_value(&Mode::Fast).unwrap(), Value::Text("Fast".into()));
+    assert_eq!(to_value(&expected).unwrap(), Value::Block(expected.clone()));
+
+    // a block written from a value is the same as from the struct
+    assert_eq!(
+        to_string("Server", &expected).unwrap(),
+        to_string("Server", &server).unwrap()
+    );
+    assert_eq!(
+        to_pretty_string("Server", &Value::Block(expected.clone())).unwrap(),
+        to_pretty_string("Server", &server).unwrap()
+    );
+
+    let i = r#"Server { host: text("localhost") ports: list(int(80)) tls: Tls { enabled: bool(no) } }
+Client { retries: int(3) }"#;
+    let document: Document = from_str_document(i).unwrap();
+    assert_eq!(document, Document::parse(i).unwrap());
+    assert_eq!(
+        Document::parse(&to_string_document(&document).unwrap()).unwrap(),
+        document
+    );
+
+    let block: Block = from_str(i).unwrap();
+    assert_eq!(block, document.get_blocks()[0]);
+    let value: Value = from_str(i).unwrap();
+    assert_eq!(value, Value::Block(block.clone()));
+    assert_eq!(from_value::<Value>(value.clone()).unwrap(), value);
+
+    // blocks are structs with a name and fields in other formats
+    let json = serde_json::to_value(&document.get_blocks()[1]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "name": "Client", "fields": { "retries": 3 } })
+    );
+
+    let flat =
+        Document::parse(r#"A { x: list(float(1.5), none) } B { y: map("k": text("v")) }"#).unwrap();
+    let json = serde_json::to_string(&flat).unwrap();
+    assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), flat);
+
+    // nested blocks are tagged, so that they are read back as blocks
+    let json = serde_json::to_value(&document).unwrap();
+    assert_eq!(
+        json[0]["fields"]["tls"],
+        serde_json::json!({
+            "$libsirt::Block": { "name": "Tls", "fields": { "enabled": false } }
+        })
+    );
+    assert_eq!(serde_json::from_value::<Document>(json).unwrap(), document);
+
+    // while a map with a name and fields is still a map
+    let value: Value = serde_json::from_str(r#"{ "name": "B", "fields": { "x": 1 } }"#).unwrap();
+    assert!(matches!(value, Value::Map(_)));
+    assert_eq!(
+        serde_json::from_value::<Value>(serde_json::to_value(&value).unwrap()).unwrap(),
+        value
+    );
+
+    let i = r#"A { m: map("name": text("B"), "fields": map()) }"#;
+    let value: Value = from_str(i).unwrap();
+    assert!(value.as_block().unwrap().get_map("m").is_ok());
+    assert_eq!(from_value::<Value>(value.clone()).unwrap(), value);
+
+    let value: Value = serde_json::from_str(r#"{ "a": [1, true, null, "text"] }"#).unwrap();
+    assert_eq!(
+        value.as_map().unwrap()["a"],
+        Value::List(vec![
+            Value::Int(1),
+            Value::Bool(true),
+            Value::None,
+            Value::Text("text".into())
+        ])
+    );
+
+    let err = to_value(&u64::MAX).unwrap_err();
+    assert!(matches!(err, Error::Serialize(_)));
+}
+
+#[test]
+fn test_to_writer() {
+    #[derive(Serialize)]
+    struct Package {
+        name: String,
+        tags: Vec<String>,
+        meta: HashMap<String, f64>,
+    }
+
+    let package = Package {
+        name: "quoted \"name\"\n".to_string(),
+        tags: vec!["a\tb".to_string(), "\u{7}".to_string()],
+        meta: HashMap::from([("score".to_string(), -1.5)]),
+    };
+
+    let mut bytes = Vec::new();
+    to_writer(&mut bytes, "Package", &package).unwrap();
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        to_string("Package", &package).unwrap()
+    );
+
+    let mut bytes = Vec::new();
+    to_writer_pretty(&mut bytes, "Package", &package).unwrap();
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        to_pretty_string("Package", &package).unwrap()
+    );
+
+    let mut s = String::new();
+    to_fmt_writer(&mut s, "Package", &package).unwrap();
+    assert_eq!(s, to_string("Package", &package).unwrap());
+
+    let mut s = String::new();
+    to_fmt_writer_pretty(&mut s, "Package", &package).unwrap();
+    assert_eq!(s, to_pretty_string("Package", &package).unwrap());
+
+    // a failing writer returns its own error
+    let mut full = [0u8; 8];
+    let err = to_writer(&mut full[..], "Package", &package).unwrap_err();
+    assert!(matches!(err, Error::Io(err) if err.kind() == std::io::ErrorKind::WriteZero));
+
+    // serialize errors are still returned as before
+    let mut bytes = Vec::new();
+    let err = to_writer(&mut bytes, "Number", &10).unwrap_err();
+    assert!(matches!(err, Error::Serialize(_)));
+}
+
+#[test]
+fn test_field_order() {
+    let i = "Env { zeta: int(1) alpha: int(2) mid: int(3) }";
+    let env: libsirt::types::Map<i64> = from_str(i).unwrap();
+
+    let keys: Vec<_> = env.keys().map(String::as_str).collect();
+    assert_eq!(keys, ["zeta", "alpha", "mid"]);
+    assert_eq!(to_string("Env", &env).unwrap(), i);
+}
+
+#[test]
+fn test_from_str_with() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Feature {
+        name: String,
+        enabled: bool,
+        tags: Vec<String>,
+    }
+
+    let i = r#"
+    Feature { name: text("blur") enabled: bool(on) tags: list(text("ui"),) }
+    Feature { name: text("logs") enabled: bool(0) tags: list() }
+    "#;
+
+    let options = ParseOptions::new()
+        .lenient_bools(true)
+        .trailing_commas(true);
+    let feature: Feature = from_str_with(i, &options).unwrap();
+    assert_eq!(
+        feature,
+        Feature {
+            name: "blur".to_string(),
+            enabled: true,
+            tags: vec!["ui".to_string()],
+        }
+    );
+
+    let enabled: Vec<bool> = from_str_named_iter_with::<Feature>(i, "Feature", &options)
+        .unwrap()
+        .map(|feature| feature.unwrap().enabled)
+        .collect();
+    assert_eq!(enabled, [true, false]);
+
+    let Err(Error::Parse(err)) = from_str::<Feature>(i) else {
+        panic!("expected a parse error");
+    };
+    assert_eq!(err.kind(), ParseErrorKind::Bool);
+}
+
+#[test]
+fn test_borrowed() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Package<'a> {
+        name: &'a str,
+        #[serde(borrow)]
+        description: Cow<'a, str>,
+        #[serde(borrow)]
+        authors: Vec<&'a str>,
+        #[serde(borrow)]
+        deps: BTreeMap<&'a str, &'a str>,
+    }
+
+    let i = r#"Package {
+        name: text("sirt")
+        description: text("a \"simple\" format")
+        authors: list(text(r"rohan"))
+        deps: map("serde": text("1.0"))
+    }"#;
+
+    let package: Package = from_str(i).unwrap();
+    assert_eq!(package.name, "sirt");
+    assert_eq!(package.authors, ["rohan"]);
+    assert_eq!(package.deps["serde"], "1.0");
+
+    // text with escapes has to be copied
+    assert_eq!(package.description, r#"a "simple" format"#);
+    assert!(matches!(package.description, Cow::Owned(_)));
+
+    let err = from_str::<Package>(r#"Package { name: text("\n") }"#).unwrap_err();
+    assert!(
+        err.source()
+            .unwrap()
+            .to_string()
+            .starts_with("Package.name: "),
+        "{err}"
+    );
+
+    // blocks can outlive the input once owned
+    let block = {
+        let i = String::from(r#"Owned { name: text("sirt") }"#);
+        parse_input(&i).unwrap().remove(0).into_owned()
+    };
+    assert_eq!(block.get_name(), "Owned");
+    assert_eq!(block.get_fields()["name"], Value::Text("sirt".into()));
+}
+
+#[test]
+fn test_error_paths() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Package {
+        name: String,
+        authors: Vec<String>,
+    }
+
+    let i = r#"
+    Package {
+        name: text("sirt")
+        authors: list(text("a"), int(2))
+    }
+    "#;
+
+    let err = from_str::<Package>(i).unwrap_err();
+    assert!(matches!(err, Error::Deserialize(_)));
+    assert_eq!(
+        err.path().unwrap(),
+        [
+            PathSegment::Block("Package".to_string()),
+            PathSegment::Field("authors".to_string()),
+            PathSegment::Index(1),
+        ]
+    );
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Package.authors[1]: expected text, found int at line 4, column 18"
+    );
+
+    #[derive(Debug, Deserialize)]
+    struct Env {
+        #[allow(dead_code)]
+        vars: HashMap<String, i64>,
+    }
+
+    let err = from_str::<Env>(r#"Env { vars: map("home": text("~")) }"#).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        r#"Env.vars["home"]: expected int, found text at line 1, column 13"#
+    );
+
+    #[derive(Serialize)]
+    struct Sizes {
+        sizes: Vec<u64>,
+    }
+
+    let err = to_string(
+        "Sizes",
+        &Sizes {
+            sizes: vec![1, u64::MAX],
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, Error::Serialize(_)));
+    assert!(
+        err.source()
+            .unwrap()
+            .to_string()
+            .starts_with("Sizes.sizes[1]: integer")
+    );
+}
+
+#[test]
+fn test_error_source() {
+    #[derive(Debug, Deserialize)]
+    struct Point {
+        #[allow(dead_code)]
+        x: i64,
+    }
+
+    let err = from_str::<Point>("Point { x: int(1) ").unwrap_err();
+    let source = err.source().unwrap();
+    let parse = source.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(parse.line(), 1);
+    assert_eq!(err.span(), Some(parse.span()));
+
+    // the message of the source is not repeated
+    assert_eq!(err.to_string(), "failed to parse input");
+}
+
+#[test]
+fn test_enums() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum External {
+        Unit,
+        Newtype(i64),
+        Tuple(i64, String),
+        Struct { x: i64, y: i64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Unit,
+        Struct { x: i64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Unit,
+        Newtype(String),
+        Struct { x: i64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Untagged {
+        Unit,
+        Number(i64),
+        Struct { name: String },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Enums {
+        external: Vec<External>,
+        internal: Vec<Internal>,
+        adjacent: Vec<Adjacent>,
+        untagged: Vec<Untagged>,
+    }
+
+    let enums = Enums {
+        external: vec![
+            External::Unit,
+            External::Newtype(1),
+            External::Tuple(2, "two".to_string()),
+            External::Struct { x: 3, y: 4 },
+        ],
+        internal: vec![Internal::Unit, Internal::Struct { x: 5 }],
+        adjacent: vec![
+            Adjacent::Unit,
+            Adjacent::Newtype("six".to_string()),
+            Adjacent::Struct { x: 7 },
+        ],
+        untagged: vec![
+            Untagged::Unit,
+            Untagged::Number(8),
+            Untagged::Struct {
+                name: "nine".to_string(),
+            },
+        ],
+    };
+
+    let s = to_string("Enums", &enums).unwrap();
+    assert_eq!(from_str::<Enums>(&s).unwrap(), enums);
+
+    let s = to_pretty_string("Enums", &enums).unwrap();
+    assert_eq!(from_str::<Enums>(&s).unwrap(), enums);
+
+    let i = r#"Enums {
+        external: list(
+            text("Unit"),
+            map("Newtype": int(1)),
+            map("Tuple": list(int(2), text("two"))),
+            Struct { x: int(3) y: int(4) }
+        )
+        internal: list()
+        adjacent: list()
+        untagged: list()
+    }"#;
+    assert_eq!(from_str::<Enums>(i).unwrap().external, enums.external);
+}
+
+#[test]
+fn test_deserialize_any() {
+    let i = r#"Server {
+        host: text("localhost")
+        port: int(8080)
+        ratio: float(0.5)
+        tags: list(text("a"), none)
+        limits: map("cpu": int(2))
+        tls: Tls { enabled: bool(yes) }
+    }"#;
+
+    let json: serde_json::Value = from_str(i).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "host": "localhost",
+            "port": 8080,
+            "ratio": 0.5,
+            "tags": ["a", null],
+            "limits": { "cpu": 2 },
+            "tls": { "enabled": true },
+        })
+    );
+
+    let fields: HashMap<String, Value> = from_str(i).unwrap();
+    assert_eq!(fields["port"], Value::Int(8080));
+    assert_eq!(
+        fields["tls"],
+        Value::Block(Block::new("Tls").with_field("enabled", true))
+    );
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Server {
+        host: String,
+        #[serde(flatten)]
+        rest: HashMap<String, serde_json::Value>,
+    }
+
+    let server: Server = from_str(i).unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.rest.len(), 5);
+    assert_eq!(server.rest["tls"], serde_json::json!({ "enabled": true }));
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Address {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Flattened {
+        #[serde(flatten)]
+        address: Address,
+        tls: Option<Tls>,
+        backup: Option<Address>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Tls {
+        enabled: bool,
+    }
+
+    let flattened: Flattened =
+        from_str(r#"Server { host: text("a") port: int(1) tls: Tls { enabled: bool(no) } }"#)
+            .unwrap();
+    assert_eq!(
+        flattened,
+        Flattened {
+            address: Address {
+                host: "a".to_string(),
+                port: 1
+            },
+            tls: Some(Tls { enabled: false }),
+            backup: None,
+        }
+    );
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(untagged)]
+    enum Config {
+        Address(Address),
+        Path { path: String },
+    }
+
+    let config: Config = from_str(r#"Config { path: text("/tmp") }"#).unwrap();
+    assert_eq!(
+        config,
+        Config::Path {
+            path: "/tmp".to_string()
+        }
+    );
+
+    let configs: Vec<Config> = from_str_document(
+        r#"Config { host: text("a") port: int(1) } Config { path: text("/tmp") }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        configs[0],
+        Config::Address(Address {
+            host: "a".to_string(),
+            port: 1
+        })
+    );
+}
+
+#[test]
+fn test_float_round_trip() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Floats {
+        double: f64,
+        single: f32,
+    }
+
+    let doubles = [
+        0.0,
+        -0.0,
+        1.0,
+        -1.5,
+        0.1,
+        1.0 / 3.0,
+        1e300,
+        -1e-300,
+        1e16,
+        123456789.0,
+        f64::MAX,
+        f64::MIN,
+        f64::MIN_POSITIVE,
+        f64::EPSILON,
+        5e-324,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+    ];
+    let singles = [
+        0.0,
+        -0.0,
+        0.1,
+        1.0 / 3.0,
+        f32::MAX,
+        f32::MIN_POSITIVE,
+        1e-45,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+    ];
+
+    for (double, single) in doubles.into_iter().zip(singles.into_iter().cycle()) {
+        let floats = Floats { double, single };
+
+        for s in [
+            to_string("Floats", &floats).unwrap(),
+            to_pretty_string("Floats", &floats).unwrap(),
+        ] {
+            let back: Floats = from_str(&s).unwrap();
+            assert_eq!(back.double.to_bits(), double.to_bits(), "{s}");
+            assert_eq!(back.single.to_bits(), single.to_bits(), "{s}");
+        }
+    }
+
+    let nan = Floats {
+        double: f64::NAN,
+        single: f32::NAN,
+    };
+    let back: Floats = from_str(&to_string("Floats", &nan).unwrap()).unwrap();
+    assert!(back.double.is_nan());
+    assert!(back.single.is_nan());
+
+    // an f32 is the same value as the float it is written as
+    assert_eq!(to_value(&0.1f32).unwrap(), Value::Float(0.1));
+    assert_eq!(to_value(&f32::MAX).unwrap(), Value::Float(3.4028235e38));
+}
+
+#[test]
+fn test_display() {
+    let i = r#"Server {
+        name: text("main \"one\"\n")
+        port: int(-8080)
+        ratio: float(2.0)
+        huge: float(-inf)
+        enabled: bool(true)
+        backup: none
+        hosts: list(text("a"), list(), map())
+        limits: map("max connections": int(10), "timeout": float(0.5))
+        tls: Tls { enabled: bool(false) cert: Cert { path: text(r"C:\cert") } }
+    }
+    Empty {}"#;
+    let document = Document::parse(i).unwrap();
+
+    let compact = document.to_string();
+    let pretty = format!("{document:#}");
+    assert_eq!(compact.lines().count(), 2);
+    assert_eq!(Document::parse(&compact).unwrap(), document);
+    assert_eq!(Document::parse(&pretty).unwrap(), document);
+    assert_eq!(compact, to_string_document(&document).unwrap());
+    assert_eq!(pretty, to_pretty_string_document(&document).unwrap());
+
+    let block = document.get(0).unwrap();
+    assert_eq!(
+        parse_input(&block.to_string()).unwrap(),
+        std::slice::from_ref(block)
+    );
+    assert_eq!(
+        parse_input(&format!("{block:#}")).unwrap(),
+        std::slice::from_ref(block)
+    );
+    assert_eq!(
+        document.get(1).unwrap().to_string(),
+        to_string("Empty", &Block::new("Empty")).unwrap()
+    );
+
+    let tls = block.get("tls").unwrap();
+    assert_eq!(
+        tls.to_string(),
+        r#"Tls { enabled: bool(false) cert: Cert { path: text("C:\\cert") } }"#
+    );
+    assert_eq!(
+        format!("{tls:#}"),
+        "Tls {\n\tenabled: bool(false)\n\tcert: Cert {\n\t\tpath: text(\"C:\\\\cert\")\n\t}\n}"
+    );
+    assert_eq!(block.get("port").unwrap().to_string(), "int(-8080)");
+    assert_eq!(block.get("ratio").unwrap().to_string(), "float(2.0)");
+    assert_eq!(block.get("backup").unwrap().to_string(), "none");
+    assert_eq!(
+        block.get("limits").unwrap().to_string(),
+        r#"map("max connections": int(10), "timeout": float(0.5))"#
+    );
+
+    assert_eq!(document.to_sirt_string().unwrap(), compact);
+    assert_eq!(block.to_pretty_sirt_string().unwrap(), format!("{block:#}"));
+    assert_eq!(tls.to_sirt_string().unwrap(), tls.to_string());
+
+    // names that Sirt can't read back are still written,
+    // and only fail when they are checked
+    let invalid = Block::new("Not valid").with_field("x", 1);
+    assert_eq!(invalid.to_string(), "Not valid { x: int(1) }");
+    assert!(matches!(invalid.to_sirt_string(), Err(Error::Serialize(_))));
+
+    let invalid = Value::from(Block::new("B").with_field("not valid", 1));
+    assert_eq!(format!("{invalid:#}"), "B {\n\tnot valid: int(1)\n}");
+    let err = invalid.to_pretty_sirt_string().unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "'not valid' cannot be used as the name of a field"
+    );
+}
@@ -9,7 +9,7 @@ use iced::{
         text_editor::{self, Action, Edit},
     },
 };
-use libsirt::Block as SirtBlock;
+use libsirt::{Block as SirtBlock, span::SourceMap};
 
 #[derive(Debug, Clone)]
 enum Message {
@@ -42,14 +42,11 @@ impl Default for App {
 }
 
 impl App {
-    fn new(blocks: Vec<SirtBlock>) -> Self {
+    fn new(blocks: Vec<SirtBlock>, source_map: SourceMap) -> Self {
         let blocks = blocks
             .into_iter()
-            .map(|block| BlockItem {
-                block,
-                description: None,
-                syntax: None,
-            })
+            .zip(source_map)
+            .map(|(block, spans)| BlockItem::new(block, spans))
             .collect();
 
         Self {
@@ -193,8 +190,8 @@ impl App {
     }
 }
 
-pub fn run(blocks: Vec<crate::Block>) -> iced::Result {
-    let app = App::new(blocks);
+pub fn run(blocks: Vec<crate::Block>, source_map: SourceMap) -> iced::Result {
+    let app = App::new(blocks, source_map);
     iced::application(move || app.clone(), App::update, App::view)
         .theme(App::theme)
         .title(App::title)
@@ -5,25 +5,57 @@ mod tui;
 
 use clap::Parser;
 use color_eyre::{
-    Result, Section, SectionExt,
+    Report, Result, Section, SectionExt,
     eyre::{ensure, eyre},
 };
 use libsirt::{
     Block,
-    error::ParseError,
-    parse_input,
+    error::{ParseError, ParseErrorKind},
+    parse_input_spanned,
+    span::SourceMap,
     types::{Float, Int},
 };
 
 use crate::cli::{Sirt, SirtCommand, Using};
 
-fn run(using: Using, blocks: Vec<Block>) -> Result<()> {
+fn run(using: Using, blocks: Vec<Block>, source_map: SourceMap) -> Result<()> {
     match using {
-        Using::Tui => ratatui::run(|term| tui::App::new(blocks).run(term)),
-        Using::Gui => gui::run(blocks).map_err(|err| err.into()),
+        Using::Tui => ratatui::run(|term| tui::App::new(blocks, source_map).run(term)),
+        Using::Gui => gui::run(blocks, source_map).map_err(|err| err.into()),
     }
 }
 
+fn parse_error(err: ParseError) -> Report {
+    let note = match err.kind() {
+        ParseErrorKind::Bool => {
+            format!("supported boolean values: [{}]", err.expected().join(", "))
+        }
+        ParseErrorKind::Int => format!("int must be between {} and {}", Int::MIN, Int::MAX),
+        ParseErrorKind::Float => {
+            format!("float must be between {} and {}", Float::MIN, Float::MAX)
+        }
+        ParseErrorKind::Text => "supported escapes: \\\" \\\\ \\n \\t \\r \\0 \\u{...}".to_string(),
+        ParseErrorKind::DuplicateKey => "each key can only appear once".to_string(),
+        ParseErrorKind::DepthLimit
+        | ParseErrorKind::InputSizeLimit
+        | ParseErrorKind::ValueCountLimit
+        | ParseErrorKind::TextLengthLimit => "the input is too large to be parsed".to_string(),
+        ParseErrorKind::Syntax if !err.expected().is_empty() => {
+            format!("expected one of: {}", err.expected().join(", "))
+        }
+        ParseErrorKind::Syntax => "Check for syntax errors and try again".to_string(),
+    };
+
+    eyre!(
+        "{} at line {}, column {}",
+        err.message(),
+        err.line(),
+        err.column()
+    )
+    .with_section(|| err.snippet().to_string().header("Parse Error:"))
+    .with_note(|| note)
+}
+
 fn main() -> Result<()> {
     color_eyre::install()?;
 
@@ -31,24 +63,8 @@ fn main() -> Result<()> {
 
     match args.command {
         SirtCommand::From { input, using } => {
-            let blocks = parse_input(&input);
-
-            match blocks {
-                Ok(blocks) => run(using, blocks)?,
-                Err(err) => {
-                    return Err(eyre!("Error returned during parsing")
-                        .with_section(|| err.to_string().header("Parse Error:"))
-                        .with_note(|| match err {
-                            ParseError::Bool(_) => {
-                                "supported boolean values: [true, false, yes, no]".to_string()
-                            }
-                            ParseError::Int(_) => {
-                                format!("int must be between {} and {}", Int::MIN, Int::MAX)
-                            }
-                            d => format!("Check for syntax errors and try again: {d}"),
-                        }));
-                }
-            }
+            let (blocks, source_map) = parse_input_spanned(&input).map_err(parse_error)?;
+            run(using, blocks, source_map)?;
         }
         SirtCommand::File { path, using } => {
             ensure!(
@@ -58,27 +74,8 @@ fn main() -> Result<()> {
             );
 
             let file = std::fs::read_to_string(path)?;
-            let blocks = parse_input(&file);
-
-            match blocks {
-                Ok(blocks) => run(using, blocks)?,
-                Err(err) => {
-                    return Err(eyre!("Error returned during parsing")
-                        .with_section(|| err.to_string().header("Parse Error:"))
-                        .with_note(|| match err {
-                            ParseError::Bool(_) => {
-                                "supported boolean values: [true, false, yes, no]".to_string()
-                            }
-                            ParseError::Int(_) => {
-                                format!("int must be between {} and {}", Int::MIN, Int::MAX)
-                            }
-                            ParseError::Float(_) => {
-                                format!("float must be between {} and {}", Float::MIN, Float::MAX)
-                            }
-                            _ => "Check for syntax errors and try again".to_owned(),
-                        }));
-                }
-            }
+            let (blocks, source_map) = parse_input_spanned(&file).map_err(parse_error)?;
+            run(using, blocks, source_map)?;
         }
     }
 
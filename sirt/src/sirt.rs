@@ -1,13 +1,23 @@
-use libsirt::{Block, Value};
+use libsirt::{Block, Value, span::BlockSpans};
 
 #[derive(Debug, Clone)]
 pub struct BlockItem {
-    pub block: Block,
+    pub block: Block<'static>,
+    pub spans: BlockSpans,
     pub description: Option<String>,
     pub syntax: Option<String>,
 }
 
 impl BlockItem {
+    pub fn new(block: Block<'_>, spans: BlockSpans) -> Self {
+        Self {
+            block: block.into_owned(),
+            spans,
+            description: None,
+            syntax: None,
+        }
+    }
+
     fn gen_desc(&mut self) {
         if self.description.is_none() {
             self.description = Some(format!(
@@ -21,7 +31,7 @@ impl BlockItem {
         if self.syntax.is_none() {
             self.syntax = Some(format!(
                 "### SYNTAX BREAKDOWN ###\n\n{}",
-                explain_syntax(&self.block)
+                explain_syntax(&self.block, &self.spans)
             ));
         }
     }
@@ -53,6 +63,24 @@ pub fn infer_type_str(ty: &Value) -> String {
             output.push('>');
             output
         }
+        Value::Map(m) => format!(
+            "Map<{}>",
+            m.values()
+                .next()
+                .map(infer_type_str)
+                .unwrap_or("<Unknown>".to_string())
+        ),
+        Value::Block(b) => b.get_name().into(),
+        Value::None => "Option<<Unknown>>".into(),
+    }
+}
+
+fn nested_blocks<'a, 'i>(value: &'a Value<'i>, blocks: &mut Vec<&'a Block<'i>>) {
+    match value {
+        Value::Block(b) if !blocks.iter().any(|n| n.get_name() == b.get_name()) => blocks.push(b),
+        Value::List(l) => l.iter().for_each(|v| nested_blocks(v, blocks)),
+        Value::Map(m) => m.values().for_each(|v| nested_blocks(v, blocks)),
+        _ => {}
     }
 }
 
@@ -68,6 +96,15 @@ pub fn repr_block(block: &Block) -> String {
     }
     output.push('}');
 
+    let mut nested = Vec::new();
+    for value in block.get_fields().values() {
+        nested_blocks(value, &mut nested);
+    }
+    for block in nested {
+        output.push_str("\n\n");
+        output.push_str(&repr_block(block));
+    }
+
     output
 }
 
@@ -78,23 +115,33 @@ fn list_depth(value: Option<&Value>, num: usize) -> usize {
     }
 }
 
-pub fn explain_syntax(block: &Block) -> String {
+pub fn explain_syntax(block: &Block, spans: &BlockSpans) -> String {
     let mut output = String::new();
 
     let name_len = block.get_name().len();
     output.push_str(&format!("{}\n", block.get_name()));
     output.push_str(&"^".repeat(name_len));
-    output.push_str(" type: identifier\n\n");
+    output.push_str(" type: identifier\n");
+    output.push_str(&format!(
+        "line {}, column {}\n\n",
+        spans.name.line, spans.name.column
+    ));
     output.push_str("FIELDS:\n");
 
     for (name, value) in block.get_fields().iter() {
         let inferred = infer_type_str(value);
         output.push_str(&format!("{name} -> {inferred}\n"));
         output.push_str("----------\n");
+        if let Some(field) = spans.field(name) {
+            output.push_str(&format!(
+                "line {}, column {}\n",
+                field.key.line, field.key.column
+            ));
+        }
         output.push_str(&format!("depth: {}\n", list_depth(Some(value), 1)));
         output.push_str(&format!("{name} -> identifier\n{inferred} -> type\n"));
         if inferred.contains("<Unknown>") {
-            output.push_str("note: cannot infer type of list,\nas there are no items inside it");
+            output.push_str("note: cannot infer the full type,\nas there is no value inside it");
         }
         output.push_str("\n\n\n");
     }
@@ -1,5 +1,5 @@
 use color_eyre::Result;
-use libsirt::Block as SirtBlock;
+use libsirt::{Block as SirtBlock, span::SourceMap};
 use ratatui::{
     DefaultTerminal, Frame,
     buffer::Buffer,
@@ -26,14 +26,11 @@ struct BlockList {
 }
 
 impl App {
-    pub fn new(blocks: Vec<SirtBlock>) -> Self {
+    pub fn new(blocks: Vec<SirtBlock>, source_map: SourceMap) -> Self {
         let block_items = blocks
             .into_iter()
-            .map(|block| BlockItem {
-                block,
-                description: None,
-                syntax: None,
-            })
+            .zip(source_map)
+            .map(|(block, spans)| BlockItem::new(block, spans))
             .collect();
 
         Self {